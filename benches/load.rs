@@ -0,0 +1,61 @@
+//! Benchmarks loading forms with many fields.
+#[macro_use]
+extern crate lopdf;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use lopdf::{Document, Object, StringFormat};
+
+/// Saves a document with one page holding `count` text fields
+fn large_form(count: usize) -> Vec<u8> {
+    let mut doc = Document::with_version("1.7");
+    let pages_id = doc.new_object_id();
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+    });
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![page_id.into()],
+        "Count" => 1,
+    }));
+
+    let fields: Vec<Object> = (0..count)
+        .map(|i| doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "P" => page_id,
+            "Rect" => vec![0.into(), 0.into(), 100.into(), 20.into()],
+            "FT" => "Tx",
+            "T" => Object::String(format!("Field {}", i).into_bytes(), StringFormat::Literal),
+        }).into())
+        .collect();
+    let acroform_id = doc.add_object(dictionary! {
+        "Fields" => fields,
+    });
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+        "AcroForm" => acroform_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    let mut buffer = Vec::new();
+    doc.save_to(&mut buffer).unwrap();
+    buffer
+}
+
+fn load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("load_form");
+    group.sample_size(20);
+    for count in &[500, 5000] {
+        let bytes = large_form(*count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &bytes, |b, bytes| {
+            b.iter(|| pdfformfill::load_form(bytes))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
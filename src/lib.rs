@@ -10,10 +10,11 @@ mod pdfformfill;
 mod utils;
 
 use wasm_bindgen::prelude::*;
-use crate::pdfformfill::Form;
+use crate::pdfformfill::{Form, FieldError};
 use wasm_bindgen::__rt::std::io::{BufReader};
 use crate::utils::set_panic_hook;
 use std::collections::HashMap;
+use serde::Serialize;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
@@ -21,6 +22,24 @@ use std::collections::HashMap;
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
+/// The object a rejected `JsForm::fill` hands to JavaScript
+#[derive(Serialize)]
+struct JsFieldError<'a> {
+    field: &'a str,
+    value: &'a str,
+    error: String,
+}
+
+impl<'a> From<&'a FieldError> for JsFieldError<'a> {
+    fn from(error: &'a FieldError) -> Self {
+        JsFieldError {
+            field: error.field(),
+            value: error.value(),
+            error: error.error().to_string(),
+        }
+    }
+}
+
 #[wasm_bindgen]
 pub struct JsForm {
     form: Form
@@ -39,7 +58,7 @@ impl JsForm {
 #[wasm_bindgen]
 impl JsForm {
     pub fn get_field_names(&self) -> Box<[JsValue]> {
-        let names = self.form.get_field_names();
+        let names = self.form.get_field_names_sorted();
 
         let result: Vec<JsValue> = names.iter().map(|x| JsValue::from(x)).collect();
 
@@ -49,7 +68,9 @@ impl JsForm {
     pub fn fill(&mut self, fields: JsValue) -> Result<(), JsValue> {
         let map: HashMap<String, String> = serde_wasm_bindgen::from_value(fields)?;
 
-        self.form.fill(map).map_err(|x| serde_wasm_bindgen::to_value(&x).unwrap())?;
+        self.form.fill(map).map_err(|x| {
+            serde_wasm_bindgen::to_value(&JsFieldError::from(&x)).unwrap_or_else(|e| e.into())
+        })?;
 
         Ok(())
     }
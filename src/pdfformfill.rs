@@ -1,4 +1,4 @@
-use lopdf::{Document, Object, ObjectId, StringFormat, Error, Dictionary};
+use lopdf::{Document, Object, ObjectId, StringFormat, Error, Dictionary, Stream};
 
 use std::{str, io};
 
@@ -8,6 +8,14 @@ use std::collections::VecDeque;
 use wasm_bindgen::__rt::std::collections::HashMap;
 use serde::{Serialize};
 
+bitflags! {
+    struct FieldFlags: u32 {
+        const READ_ONLY         = 1;
+        const REQUIRED          = 1 << 1;
+        const NO_EXPORT         = 1 << 2;
+    }
+}
+
 bitflags! {
     struct ButtonFlags: u32 {
         const NO_TOGGLE_TO_OFF  = 1 << 14;
@@ -18,6 +26,18 @@ bitflags! {
     }
 }
 
+bitflags! {
+    struct TextFlags: u32 {
+        const MULTILINE         = 1 << 12;
+        const PASSWORD          = 1 << 13;
+        const FILE_SELECT       = 1 << 20;
+        const DO_NOT_SPELLCHECK = 1 << 22;
+        const DO_NOT_SCROLL     = 1 << 23;
+        const COMB              = 1 << 24;
+        const RICH_TEXT         = 1 << 25;
+    }
+}
+
 bitflags! {
     struct ChoiceFlags: u32 {
         const COBMO             = 0x20000;
@@ -38,11 +58,76 @@ bitflags! {
 pub struct Form {
     doc: Document,
     form_fields: HashMap<String, ObjectId>,
+    /// The names of `form_fields` in the order the fields were found in the document
+    field_order: Vec<String>,
+    name_separator: char,
+    /// The AcroForm dictionary of the document catalog
+    acroform_id: ObjectId,
+    appearance_mode: AppearanceMode,
+}
+
+/// How setters update the appearance streams of the fields they change
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AppearanceMode {
+    /// Remove the outdated appearance and rely on the viewer to regenerate it. This is the default.
+    Rely,
+    /// Write a new appearance for text fields and combo boxes, for viewers that ignore
+    /// `NeedAppearances`. Text is drawn on a single line.
+    Regenerate,
+}
+
+/// The outcome of searching a document for its fields
+struct Discovery {
+    acroform_id: ObjectId,
+    form_fields: HashMap<String, ObjectId>,
+    field_order: Vec<String>,
+    /// The number of fields that take input but have no name
+    unnamed: usize,
+    /// The fields that could not be read
+    errors: Vec<(ObjectId, LoadError)>,
+}
+
+/// Options that control how a form is loaded
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    /// The separator between the partial names of a field and its parents in full names
+    pub name_separator: char,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions { name_separator: '.' }
+    }
+}
+
+/// The repairs `Form::normalize` applies. All of them are enabled by default.
+#[derive(Debug, Clone)]
+pub struct NormalizeOptions {
+    /// Set the appearance states of check boxes and radio buttons to their values, see
+    /// `Form::repair_appearance_states`
+    pub sync_appearance_states: bool,
+    /// Set `NeedAppearances`, so viewers regenerate the appearances of text and choice fields
+    pub need_appearances: bool,
+    /// Set the print flag of every widget, so filled in fields show up on paper
+    pub print_widgets: bool,
+    /// Remove the XFA form, see `Form::drop_xfa`
+    pub drop_xfa: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        NormalizeOptions {
+            sync_appearance_states: true,
+            need_appearances: true,
+            print_widgets: true,
+            drop_xfa: true,
+        }
+    }
 }
 
 /// The possible types of fillable form fields in a PDF
 #[wasm_bindgen]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FieldType {
     Button,
     Radio,
@@ -55,8 +140,8 @@ pub enum FieldType {
 /// The current state of a form field
 #[derive(Debug)]
 pub enum FieldState {
-    /// Push buttons have no state
-    Button,
+    /// Push buttons have no state, only the action they trigger when clicked
+    Button { action: Option<ButtonAction> },
     /// `selected` is the sigular option from `options` that is selected
     Radio { selected: String, options: Vec<String> },
     /// The toggle state of the checkbox
@@ -69,6 +154,68 @@ pub enum FieldState {
     Text { text: String },
 }
 
+/// The action a push button triggers when clicked
+#[derive(Debug, Clone, PartialEq)]
+pub enum ButtonAction {
+    /// Sends the form data to `url`
+    SubmitForm { url: String },
+    /// Resets form fields to their default values. If `exclude` is false, these are the fields
+    /// listed in `fields`, or all fields if the list is empty. If `exclude` is true, these are all
+    /// fields except the listed ones.
+    ResetForm { fields: Vec<String>, exclude: bool },
+    /// Runs a JavaScript
+    JavaScript { script: String },
+}
+
+/// The kind of value a text field is formatted as by its Acrobat format script
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldFormat {
+    /// A number, formatted by `AFNumber_Format`
+    Number,
+    /// A number with a currency symbol, formatted by `AFNumber_Format`
+    Currency,
+    /// A percentage, formatted by `AFPercent_Format`
+    Percent,
+    /// A date, formatted by `AFDate_Format` or `AFDate_FormatEx`
+    Date,
+    /// A time, formatted by `AFTime_Format` or `AFTime_FormatEx`
+    Time,
+    /// A zip code, phone or social security number, formatted by `AFSpecial_Format`
+    Special,
+}
+
+/// A color of the text of a field, with components from 0 to 1
+#[derive(Debug, Clone, PartialEq)]
+pub enum Color {
+    Gray(f64),
+    Rgb(f64, f64, f64),
+    Cmyk(f64, f64, f64, f64),
+}
+
+/// The options and selection of a list or combo box
+#[derive(Debug)]
+pub struct ChoiceSnapshot {
+    /// All options of the field
+    pub options: Vec<String>,
+    /// The selected options from `options`
+    pub selected: Vec<String>,
+    /// Whether more than one option may be selected
+    pub multiselect: bool,
+    /// Whether the user may enter a value that is not one of `options`
+    pub editable: bool,
+}
+
+/// The appearance characteristics (`MK`) of the widget of a field
+#[derive(Debug, Clone, PartialEq)]
+pub struct WidgetAppearance {
+    /// The background color, or None if the background is transparent
+    pub background: Option<Color>,
+    /// The border color, or None if the widget has no border
+    pub border: Option<Color>,
+    /// The counterclockwise rotation of the widget in degrees, a multiple of 90
+    pub rotation: i64,
+}
+
 #[derive(Debug, Error)]
 /// Errors that may occur while loading a PDF
 pub enum LoadError {
@@ -83,6 +230,14 @@ pub enum LoadError {
     NotAReference,
     /// A value that must be a certain type was not that type
     UnexpectedType,
+    /// The given number of fields has no name that could be used to address them
+    #[error(non_std, no_from)]
+    UnnamedFields(usize),
+    /// The input is larger than the allowed maximum size
+    TooLarge,
+    /// A field with the given name already exists
+    #[error(non_std, no_from)]
+    DuplicateName(String),
 }
 
 impl From<lopdf::Error> for LoadError {
@@ -119,6 +274,23 @@ impl FieldError {
     }
 }
 
+impl FieldError {
+    /// The reason the value was rejected
+    pub fn error(&self) -> &ValueError {
+        &self.error
+    }
+
+    /// The name of the field the value was meant for
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// The rejected value
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
 
 trait PdfObjectDeref {
     fn deref<'a>(&self, doc: &'a Document) -> Result<&'a Object, LoadError>;
@@ -133,6 +305,363 @@ impl PdfObjectDeref for Object {
     }
 }
 
+/// Decodes a PDF text string, which is either UTF-16BE starting with a byte order mark or
+/// PDFDocEncoding
+fn decode_text_string(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        let string_u16: Vec<u16> = bytes[2..].chunks(2)
+            .map(|pair| (u16::from(pair[0]) << 8) | u16::from(*pair.get(1).unwrap_or(&0)))
+            .collect();
+        String::from_utf16_lossy(&string_u16)
+    } else {
+        bytes.iter().map(|byte| pdf_doc_char(*byte)).collect()
+    }
+}
+
+/// Encodes a string as PDF text string. ASCII is stored as is, everything else as UTF-16BE
+/// starting with a byte order mark.
+fn encode_pdf_text_string(s: &str) -> Vec<u8> {
+    if s.is_ascii() {
+        s.as_bytes().to_vec()
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        for unit in s.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_be_bytes());
+        }
+        bytes
+    }
+}
+
+/// Encodes a partial field name for `T`. Names are always read as UTF-16, see `Form::get_form_name`.
+fn encode_field_name(name: &str) -> Object {
+    let mut bytes = vec![0xFE, 0xFF];
+    bytes.extend(name.encode_utf16().flat_map(|unit| unit.to_be_bytes().to_vec()));
+    Object::String(bytes, StringFormat::Literal)
+}
+
+/// Decodes the string value of a field, which is written either as UTF-16BE text string or, by
+/// older versions of this crate, as UTF-8. Anything else is read as PDFDocEncoding, which covers
+/// the Latin-1 values of many older forms.
+fn decode_value_string(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        decode_text_string(bytes)
+    } else {
+        match str::from_utf8(bytes) {
+            Ok(s) => s.to_owned(),
+            Err(_) => decode_text_string(bytes)
+        }
+    }
+}
+
+/// Maps a single PDFDocEncoding byte to its character. Apart from the block 0x80 - 0xA0 the
+/// encoding matches Latin-1.
+fn pdf_doc_char(byte: u8) -> char {
+    const HIGH: [char; 33] = [
+        '\u{2022}', '\u{2020}', '\u{2021}', '\u{2026}', '\u{2014}', '\u{2013}', '\u{0192}', '\u{2044}',
+        '\u{2039}', '\u{203A}', '\u{2212}', '\u{2030}', '\u{201E}', '\u{201C}', '\u{201D}', '\u{2018}',
+        '\u{2019}', '\u{201A}', '\u{2122}', '\u{FB01}', '\u{FB02}', '\u{0141}', '\u{0152}', '\u{0160}',
+        '\u{0178}', '\u{017D}', '\u{0131}', '\u{0142}', '\u{0153}', '\u{0161}', '\u{017E}', '\u{FFFD}',
+        '\u{20AC}',
+    ];
+    match byte {
+        0x80..=0xA0 => HIGH[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
+
+/// Returns true if one of the parents of a field dictionary has a name
+fn has_named_parent(doc: &Document, dict: &Dictionary) -> bool {
+    let mut current = dict.get(b"Parent").and_then(Object::as_reference).ok();
+    while let Some(parent_id) = current {
+        let parent = match doc.objects.get(&parent_id).map(Object::as_dict) {
+            Some(Ok(parent)) => parent,
+            _ => return false
+        };
+        if parent.has(b"T") {
+            return true;
+        }
+        current = parent.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    false
+}
+
+/// Moves the AcroForm dictionary into an object of its own if it is stored inline in the document
+/// catalog, and returns its id
+fn promote_acroform(doc: &mut Document) -> Result<ObjectId, LoadError> {
+    let catalog_id = doc.trailer.get(b"Root").or(Err(LoadError::DictionaryKeyNotFound))?.as_reference()?;
+    let catalog = doc.objects.get(&catalog_id).ok_or(LoadError::NoSuchReference(catalog_id))?.as_dict()?;
+    let acroform = match *catalog.get(b"AcroForm").or(Err(LoadError::DictionaryKeyNotFound))? {
+        Object::Reference(oid) => return Ok(oid),
+        Object::Dictionary(ref acroform) => acroform.clone(),
+        _ => return Err(LoadError::UnexpectedType)
+    };
+    let acroform_id = doc.add_object(acroform);
+    doc.objects.get_mut(&catalog_id).ok_or(LoadError::NoSuchReference(catalog_id))?.as_dict_mut()?.set("AcroForm", acroform_id);
+    Ok(acroform_id)
+}
+
+/// Moves field dictionaries that are stored inline in the `Fields` array into objects of their
+/// own, so every field can be addressed by an `ObjectId`
+fn promote_inline_fields(doc: &mut Document, acroform_id: ObjectId) {
+    let fields = match doc.objects.get(&acroform_id).map(Object::as_dict) {
+        Some(Ok(acroform)) => match acroform.get(b"Fields").and_then(Object::as_array) {
+            Ok(fields) => fields.clone(),
+            Err(_) => return
+        },
+        _ => return
+    };
+    if !fields.iter().any(|field| field.as_dict().is_ok()) {
+        return;
+    }
+
+    let fields: Vec<Object> = fields.into_iter().map(|field| match field {
+        Object::Dictionary(dict) => Object::Reference(doc.add_object(dict)),
+        field => field
+    }).collect();
+    if let Some(Ok(acroform)) = doc.objects.get_mut(&acroform_id).map(Object::as_dict_mut) {
+        acroform.set("Fields", fields);
+    }
+}
+
+/// Formats a page number in one of the numbering styles of page labels
+fn format_page_number(number: i64, style: &str) -> String {
+    match style {
+        "R" => to_roman(number),
+        "r" => to_roman(number).to_lowercase(),
+        // Letters run A to Z, then AA to ZZ, AAA to ZZZ and so on
+        "A" | "a" if number > 0 => {
+            let base = if style == "A" { b'A' } else { b'a' };
+            let letter = char::from(base + ((number - 1) % 26) as u8);
+            std::iter::repeat(letter).take(((number - 1) / 26 + 1) as usize).collect()
+        }
+        _ => number.to_string()
+    }
+}
+
+/// Gets the `Kids` of a field, which may be stored in an array of their own
+fn get_kids<'a>(doc: &'a Document, field: &'a Dictionary) -> Option<&'a Vec<Object>> {
+    let kids = match field.get(b"Kids").ok()? {
+        &Object::Reference(oid) => doc.objects.get(&oid)?,
+        kids => kids
+    };
+    kids.as_array().ok()
+}
+
+/// Escapes the characters of `s` that have a special meaning in XML text and attributes
+fn escape_xml(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '&' => res.push_str("&amp;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Appends a new cross-reference table to a PDF whose table is damaged. The table lists every
+/// `n g obj` header found in the file, where later definitions of an object win like in
+/// incremental updates. The trailer keeps the `Root` and `Info` of the last old trailer. Returns
+/// None if there is no trailer to take them from.
+fn rebuild_xref(buffer: &[u8]) -> Option<Vec<u8>> {
+    let object_header = regex::bytes::Regex::new(r"(?m)^[ \t]*(\d+)[ \t\r\n]+(\d+)[ \t\r\n]+obj\b").unwrap();
+    let mut offsets: std::collections::BTreeMap<u32, (u16, usize)> = std::collections::BTreeMap::new();
+    for captures in object_header.captures_iter(buffer) {
+        let number = str::from_utf8(&captures[1]).ok()?.parse().ok()?;
+        let generation = str::from_utf8(&captures[2]).ok()?.parse().ok()?;
+        // Skip the whitespace the line started with
+        let start = captures.get(1)?.start();
+        offsets.insert(number, (generation, start));
+    }
+
+    let trailer_start = buffer.windows(7).rposition(|window| window == b"trailer")?;
+    let trailer = &buffer[trailer_start..];
+    let reference = |key: &str| regex::bytes::Regex::new(&format!(r"/{}\s+(\d+\s+\d+\s+R)", key)).ok()?
+        .captures(trailer)
+        .map(|captures| captures[1].to_vec());
+    let root = reference("Root")?;
+
+    let size = offsets.keys().next_back().map_or(1, |max| max + 1);
+    let mut out = buffer.to_vec();
+    out.push(b'\n');
+    let xref_start = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n", size).as_bytes());
+    for number in 0..size {
+        let entry = match offsets.get(&number) {
+            Some(&(generation, offset)) => format!("{:010} {:05} n\r\n", offset, generation),
+            None => "0000000000 65535 f\r\n".to_owned()
+        };
+        out.extend_from_slice(entry.as_bytes());
+    }
+    out.extend_from_slice(format!("trailer\n<< /Size {} /Root ", size).as_bytes());
+    out.extend_from_slice(&root);
+    if let Some(info) = reference("Info") {
+        out.extend_from_slice(b" /Info ");
+        out.extend_from_slice(&info);
+    }
+    out.extend_from_slice(format!(" >>\nstartxref\n{}\n%%EOF\n", xref_start).as_bytes());
+    Some(out)
+}
+
+/// Writes a number as upper case roman numeral
+fn to_roman(mut number: i64) -> String {
+    const NUMERALS: [(i64, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut res = String::new();
+    for &(value, numeral) in NUMERALS.iter() {
+        while number >= value {
+            res.push_str(numeral);
+            number -= value;
+        }
+    }
+    res
+}
+
+/// Reads a color from an array of one (gray), three (RGB) or four (CMYK) components
+fn color_from_array(components: &[Object]) -> Option<Color> {
+    let components: Option<Vec<f64>> = components.iter().map(as_number).collect();
+    match components?[..] {
+        [g] => Some(Color::Gray(g)),
+        [r, g, b] => Some(Color::Rgb(r, g, b)),
+        [c, m, y, k] => Some(Color::Cmyk(c, m, y, k)),
+        _ => None
+    }
+}
+
+/// Reads a PDF number, which may be stored as an integer or a real
+fn as_number(obj: &Object) -> Option<f64> {
+    match obj {
+        &Object::Integer(i) => Some(i as f64),
+        &Object::Real(r) => Some(f64::from(r)),
+        _ => None
+    }
+}
+
+/// Finds the operator setting the text color in a default appearance string like
+/// `/Helv 12 Tf 0 0 1 rg`. Returns the color and the range of tokens that set it.
+fn parse_da_color(tokens: &[&str]) -> Option<(Color, std::ops::Range<usize>)> {
+    let mut res = None;
+    for (i, token) in tokens.iter().enumerate() {
+        let operands = match *token {
+            "g" => 1,
+            "rg" => 3,
+            "k" => 4,
+            _ => continue
+        };
+        if i < operands {
+            continue;
+        }
+        let values = match tokens[i - operands..i].iter().map(|t| t.parse::<f64>()).collect::<Result<Vec<f64>, _>>() {
+            Ok(values) => values,
+            Err(_) => continue
+        };
+        let color = match values[..] {
+            [gray] => Color::Gray(gray),
+            [r, g, b] => Color::Rgb(r, g, b),
+            [c, m, y, k] => Color::Cmyk(c, m, y, k),
+            _ => continue
+        };
+        res = Some((color, i - operands..i + 1));
+    }
+    res
+}
+
+/// Estimates the width of `text` in a Helvetica-like font of size 1
+fn estimate_text_width(text: &str) -> f64 {
+    text.chars().map(|c| match c {
+        'i' | 'j' | 'l' | 'I' | '.' | ',' | ':' | ';' | '\'' | '!' | '|' => 0.25,
+        ' ' | 'f' | 't' | 'r' | '(' | ')' | '-' | '/' => 0.33,
+        'm' | 'w' | 'M' | 'W' | '@' => 0.83,
+        c if c.is_uppercase() => 0.67,
+        _ => 0.55
+    }).sum()
+}
+
+/// Computes the font size at which `text` fits on a single line into a box of the given size,
+/// like viewers do for auto sized (`0 Tf`) text. The size is at most 12 like in Acrobat.
+fn fit_font_size(text: &str, width: f64, height: f64) -> f64 {
+    // Leave room for the border and the padding of two points on each side
+    let by_height = (height - 4.0) / 1.15;
+    let text_width = estimate_text_width(text);
+    let by_width = if text_width > 0.0 { (width - 4.0) / text_width } else { by_height };
+    by_height.min(by_width).clamp(1.0, 12.0)
+}
+
+/// Writes the content of an appearance stream that shows `text` on a single line, vertically
+/// centered in a box of the given size and styled by the default appearance `da`. Auto sized
+/// text gets the largest size that fits. The text is written in the Latin-1 subset of the
+/// standard encodings; other characters are replaced by `?`.
+fn single_line_appearance(da: &str, text: &str, width: f64, height: f64) -> Vec<u8> {
+    let mut tokens: Vec<String> = da.split_whitespace().map(str::to_owned).collect();
+    let font_size = match tokens.iter().position(|token| token == "Tf") {
+        Some(i) if i > 0 => match tokens[i - 1].parse::<f64>() {
+            // A font size of 0 means auto sized text
+            Ok(size) if size <= 0.0 => {
+                let size = fit_font_size(text, width, height);
+                tokens[i - 1] = format!("{:.2}", size);
+                size
+            }
+            Ok(size) => size,
+            Err(_) => 12.0
+        },
+        _ => 12.0
+    };
+    let hex: String = text.chars()
+        .map(|c| if (c as u32) < 0x100 { c as u32 } else { '?' as u32 })
+        .map(|byte| format!("{:02X}", byte))
+        .collect();
+    let baseline = ((height - font_size) / 2.0 + font_size * 0.22).max(0.0);
+    format!("/Tx BMC\nq\nBT\n{}\n2 {:.2} Td\n<{}> Tj\nET\nQ\nEMC\n", tokens.join(" "), baseline, hex).into_bytes()
+}
+
+/// Writes a value in PDF syntax for an FDF file. Strings are written as hex strings so they need
+/// no escaping. Returns false for objects that cannot be written inline, like streams.
+fn write_fdf_value(obj: &Object, out: &mut Vec<u8>) -> bool {
+    match *obj {
+        Object::Null => out.extend_from_slice(b"null"),
+        Object::Boolean(b) => out.extend_from_slice(if b { b"true" } else { b"false" }),
+        Object::Integer(i) => out.extend_from_slice(i.to_string().as_bytes()),
+        Object::Real(r) => out.extend_from_slice(r.to_string().as_bytes()),
+        Object::Name(ref name) => {
+            out.push(b'/');
+            for &byte in name {
+                if byte > b' ' && byte < 0x7F && !b"#/%()<>[]{}".contains(&byte) {
+                    out.push(byte);
+                } else {
+                    out.extend_from_slice(format!("#{:02X}", byte).as_bytes());
+                }
+            }
+        }
+        Object::String(ref string, _) => {
+            out.push(b'<');
+            for byte in string {
+                out.extend_from_slice(format!("{:02X}", byte).as_bytes());
+            }
+            out.push(b'>');
+        }
+        Object::Array(ref array) => {
+            out.push(b'[');
+            for (i, item) in array.iter().enumerate() {
+                if i > 0 {
+                    out.push(b' ');
+                }
+                if !write_fdf_value(item, out) {
+                    return false;
+                }
+            }
+            out.push(b']');
+        }
+        _ => return false
+    }
+    true
+}
+
 impl Form {
     /// Takes a reader containing a PDF with a fillable form, analyzes the content, and attempts to
     /// identify all of the fields the form has.
@@ -141,6 +670,17 @@ impl Form {
         Self::load_doc(doc)
     }
 
+    /// Like `load_from`, but refuses to parse inputs larger than `max_bytes`. At most
+    /// `max_bytes + 1` bytes are read from `reader` before returning `LoadError::TooLarge`.
+    pub fn load_from_limited<R: io::Read>(reader: R, max_bytes: usize) -> Result<Self, LoadError> {
+        let mut buffer = Vec::new();
+        io::Read::read_to_end(&mut reader.take(max_bytes as u64 + 1), &mut buffer)?;
+        if buffer.len() > max_bytes {
+            return Err(LoadError::TooLarge);
+        }
+        Self::load_from(&buffer[..])
+    }
+
     /// Takes a path to a PDF with a fillable form, analyzes the file, and attempts to identify all
     /// of the fields the form has.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
@@ -148,47 +688,293 @@ impl Form {
         Self::load_doc(doc)
     }
 
-    fn load_doc(doc: Document) -> Result<Self, LoadError> {
+    /// Like `load`, but survives a damaged cross-reference table. If the document cannot be read as
+    /// is, the objects are located by scanning the file and a fresh table is built for them.
+    /// Objects stored in object streams cannot be recovered this way.
+    pub fn load_recovering<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let buffer = std::fs::read(path)?;
+        match Document::load_mem(&buffer) {
+            Ok(doc) => Self::load_doc(doc),
+            Err(e) => match rebuild_xref(&buffer) {
+                Some(repaired) => Self::load_doc(Document::load_mem(&repaired)?),
+                None => Err(e.into())
+            }
+        }
+    }
+
+    /// Like `load`, but fails with `LoadError::UnnamedFields` if any field that takes input has
+    /// no usable name instead of leaving those fields out.
+    pub fn load_strict<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let mut doc = Document::load(path)?;
+        let mut discovery = Self::discover_fields(&mut doc)?;
+        if !discovery.errors.is_empty() {
+            return Err(discovery.errors.remove(0).1);
+        }
+        match discovery.unnamed {
+            0 => Ok(Self::with_fields(doc, discovery)),
+            unnamed => Err(LoadError::UnnamedFields(unnamed))
+        }
+    }
+
+    /// Like `load`, but fields that cannot be read are skipped instead of failing the whole load.
+    /// Returns the form of all readable fields together with the id of every skipped field and
+    /// the reason it was skipped. Entries of `Fields` or `Kids` that are not even a reference are
+    /// reported with the id `(0, 0)`.
+    pub fn load_collecting<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<(ObjectId, LoadError)>), LoadError> {
+        let mut doc = Document::load(path)?;
+        let mut discovery = Self::discover_fields(&mut doc)?;
+        let errors = std::mem::take(&mut discovery.errors);
+        Ok((Self::with_fields(doc, discovery), errors))
+    }
+
+    /// Like `load`, but with the given options
+    pub fn load_with_options<P: AsRef<Path>>(path: P, options: LoadOptions) -> Result<Self, LoadError> {
+        let mut form = Self::load(path)?;
+        form.name_separator = options.name_separator;
+        Ok(form)
+    }
+
+    fn load_doc(mut doc: Document) -> Result<Self, LoadError> {
+        let mut discovery = Self::discover_fields(&mut doc)?;
+        if discovery.errors.is_empty() {
+            Ok(Self::with_fields(doc, discovery))
+        } else {
+            Err(discovery.errors.remove(0).1)
+        }
+    }
+
+    fn with_fields(doc: Document, discovery: Discovery) -> Self {
+        Form {
+            doc,
+            form_fields: discovery.form_fields,
+            field_order: discovery.field_order,
+            name_separator: LoadOptions::default().name_separator,
+            acroform_id: discovery.acroform_id,
+            appearance_mode: AppearanceMode::Rely,
+        }
+    }
+
+    /// Searches the document for its fields again, for when fields were added or removed by
+    /// editing the document directly. Fails like `load` and then leaves the known fields as they
+    /// were.
+    pub fn reindex(&mut self) -> Result<(), LoadError> {
+        let mut discovery = Self::discover_fields(&mut self.doc)?;
+        if !discovery.errors.is_empty() {
+            return Err(discovery.errors.remove(0).1);
+        }
+        self.form_fields = discovery.form_fields;
+        self.field_order = discovery.field_order;
+        self.acroform_id = discovery.acroform_id;
+        Ok(())
+    }
+
+    /// Removes all objects that can no longer be reached from the document trailer, like the
+    /// widgets and appearance streams of removed fields, and returns how many were removed.
+    /// Fields among them are forgotten.
+    pub fn prune(&mut self) -> usize {
+        let removed = self.doc.prune_objects();
+        let doc = &self.doc;
+        self.form_fields.retain(|_, field_id| doc.objects.contains_key(field_id));
+        let form_fields = &self.form_fields;
+        self.field_order.retain(|name| form_fields.contains_key(name));
+        removed.len()
+    }
+
+    /// Finds all fields of the document, skipping the ones that cannot be read or have no name
+    fn discover_fields(doc: &mut Document) -> Result<Discovery, LoadError> {
+        let acroform_id = promote_acroform(doc)?;
+        promote_inline_fields(doc, acroform_id);
+        let doc = &*doc;
+        let mut unnamed = 0;
+        let mut errors = Vec::new();
         let mut queue = VecDeque::new();
         let mut map: HashMap<String, ObjectId> = HashMap::new();
-        // Block so borrow of doc ends before doc is moved into the result
+        let mut field_order = Vec::new();
         {
             // Get the form's top level fields
             let catalog = doc.trailer.get(b"Root")
                 .or(Err(LoadError::DictionaryKeyNotFound))?
-                .deref(&doc)?
+                .deref(doc)?
                 .as_dict().or(Err(LoadError::UnexpectedType))?;
             let acroform = catalog.get(b"AcroForm")
                 .or(Err(LoadError::DictionaryKeyNotFound))?
-                .deref(&doc)?
+                .deref(doc)?
                 .as_dict().or(Err(LoadError::UnexpectedType))?;
             let fields_list = acroform.get(b"Fields")
                 .or(Err(LoadError::DictionaryKeyNotFound))?
-                //    .deref(&doc)?
+                //    .deref(doc)?
                 .as_array().or(Err(LoadError::UnexpectedType))?;
-            queue.append(&mut VecDeque::from(fields_list.clone()));
+            // Most forms have at least one field per top level entry, so grow the collections
+            // once up front instead of repeatedly during the traversal
+            queue.reserve(fields_list.len());
+            queue.extend(fields_list.iter().cloned());
+            map.reserve(fields_list.len());
+            field_order.reserve(fields_list.len());
 
             // Iterate over the fields
             while let Some(objref) = queue.pop_front() {
-                let obj = objref.deref(&doc)?;
+                let obj = match objref.deref(doc) {
+                    Ok(obj) => obj,
+                    Err(e) => {
+                        errors.push((objref.as_reference().unwrap_or((0, 0)), e));
+                        continue;
+                    }
+                };
                 if let &Object::Dictionary(ref dict) = obj {
                     // If the field has FT, it actually takes input.  Save this
                     if dict.get(b"FT").is_ok() {
                         let field_id = objref.as_reference().unwrap();
 
                         if let Ok(Object::String(ref string_u8, _)) = dict.get(b"T") {
-                            let name = Form::get_form_name(string_u8.clone())?;
-                            map.insert(name, field_id);
+                            match Form::get_form_name(string_u8.clone()) {
+                                Ok(name) => {
+                                    if map.insert(name.clone(), field_id).is_none() {
+                                        field_order.push(name);
+                                    }
+                                }
+                                Err(e) => errors.push((field_id, e))
+                            }
+                        } else if !has_named_parent(doc, dict) {
+                            // Widgets that repeat the FT of their field take its name
+                            unnamed += 1;
                         }
                     }
                     // If this field has kids, they might have FT, so add them to the queue
-                    if let Ok(&Object::Array(ref kids)) = dict.get(b"Kids") {
-                        queue.append(&mut VecDeque::from(kids.clone()));
+                    if let Some(kids) = get_kids(doc, dict) {
+                        queue.extend(kids.iter().cloned());
+                    }
+                }
+            }
+        }
+        Ok(Discovery { acroform_id, form_fields: map, field_order, unnamed, errors })
+    }
+
+    /// Gets the AcroForm dictionary of the document catalog
+    fn get_acroform(&self) -> Result<&Dictionary, LoadError> {
+        Ok(self.doc.objects.get(&self.acroform_id).ok_or(LoadError::NoSuchReference(self.acroform_id))?.as_dict()?)
+    }
+
+    /// Sets how the setters update the appearances of the fields they change
+    pub fn set_appearance_mode(&mut self, mode: AppearanceMode) {
+        self.appearance_mode = mode;
+    }
+
+    /// Gets the AcroForm dictionary, which holds the form's fields, flags and default resources
+    ///
+    /// # Panics
+    /// Will panic if the AcroForm object was removed by editing the document directly
+    pub fn acroform(&self) -> &Dictionary {
+        self.get_acroform().unwrap()
+    }
+
+    /// Gets the AcroForm dictionary of the document catalog for modification
+    fn get_acroform_mut(&mut self) -> Result<&mut Dictionary, LoadError> {
+        Ok(self.doc.objects.get_mut(&self.acroform_id).ok_or(LoadError::NoSuchReference(self.acroform_id))?.as_dict_mut()?)
+    }
+
+    /// Returns true if the document was read from a cross-reference stream instead of a classic
+    /// cross-reference table. Forms are always saved with a classic table.
+    pub fn uses_xref_stream(&self) -> bool {
+        let is_xref = |dict: &Dictionary| dict.get(b"Type").and_then(Object::as_name).ok() == Some(b"XRef");
+        is_xref(&self.doc.trailer) || self.doc.trailer.has(b"XRefStm") || self.doc.objects.values().any(|object| match object.as_stream() {
+            Ok(stream) => is_xref(&stream.dict),
+            Err(_) => false
+        })
+    }
+
+    /// Gets the PDF version from the document header, e.g. "1.7"
+    pub fn pdf_version(&self) -> String {
+        self.doc.version.clone()
+    }
+
+    /// Returns true if the document was saved for fast web view, which is marked by a
+    /// linearization parameter dictionary
+    pub fn is_linearized(&self) -> bool {
+        self.doc.objects.values().any(|object| match object.as_dict() {
+            Ok(dict) => dict.has(b"Linearized"),
+            Err(_) => false
+        })
+    }
+
+    /// Returns true if the form also carries an XFA form, which some viewers prefer over the
+    /// AcroForm fields
+    pub fn has_xfa(&self) -> bool {
+        self.get_acroform().map(|acroform| acroform.has(b"XFA")).unwrap_or(false)
+    }
+
+    /// Removes the XFA form so viewers fall back to the AcroForm fields filled by this crate
+    pub fn drop_xfa(&mut self) {
+        if let Ok(acroform) = self.get_acroform_mut() {
+            acroform.remove(b"XFA");
+        }
+    }
+
+    /// Removes the document-level JavaScript (the `JavaScript` name tree) and the additional
+    /// actions (`AA`) of all fields and their widgets, so viewers run no scripts of the form
+    pub fn strip_javascript(&mut self) {
+        let names_id = match self.doc.trailer.get(b"Root").and_then(Object::as_reference) {
+            Ok(catalog_id) => match self.doc.objects.get_mut(&catalog_id).map(Object::as_dict_mut) {
+                Some(Ok(catalog)) => match catalog.get_mut(b"Names") {
+                    Ok(&mut Object::Reference(names_id)) => Some(names_id),
+                    Ok(&mut Object::Dictionary(ref mut names)) => {
+                        names.remove(b"JavaScript");
+                        None
                     }
+                    _ => None
+                },
+                _ => None
+            },
+            Err(_) => None
+        };
+        if let Some(Some(Ok(names))) = names_id.map(|names_id| self.doc.objects.get_mut(&names_id).map(Object::as_dict_mut)) {
+            names.remove(b"JavaScript");
+        }
+
+        let field_ids: Vec<ObjectId> = self.form_fields.values().cloned().collect();
+        for field_id in field_ids {
+            for dict_id in self.get_widget_ids(field_id).into_iter().chain(std::iter::once(field_id)) {
+                if let Some(Ok(dict)) = self.doc.objects.get_mut(&dict_id).map(Object::as_dict_mut) {
+                    dict.remove(b"AA");
                 }
             }
         }
-        Ok(Form { doc, form_fields: map })
+    }
+
+    /// Returns true if the document catalog has an `OpenAction`, which viewers perform when the
+    /// document is opened, like running a script or going to a page
+    pub fn has_open_action(&self) -> bool {
+        self.doc.trailer.get(b"Root").ok()
+            .and_then(|catalog| catalog.deref(&self.doc).ok())
+            .and_then(|catalog| catalog.as_dict().ok())
+            .map(|catalog| catalog.has(b"OpenAction"))
+            .unwrap_or(false)
+    }
+
+    /// Returns true if the document is tagged for accessibility, meaning its catalog marks it as
+    /// tagged (`MarkInfo/Marked`) and has a structure tree (`StructTreeRoot`)
+    pub fn is_tagged(&self) -> bool {
+        let catalog = match self.doc.trailer.get(b"Root").ok()
+            .and_then(|catalog| catalog.deref(&self.doc).ok())
+            .and_then(|catalog| catalog.as_dict().ok()) {
+            Some(catalog) => catalog,
+            None => return false
+        };
+        let is_marked = catalog.get(b"MarkInfo").ok()
+            .and_then(|mark_info| self.resolve_object(mark_info).ok())
+            .and_then(|mark_info| mark_info.as_dict().ok())
+            .map(|mark_info| matches!(mark_info.get(b"Marked"), Ok(&Object::Boolean(true))))
+            .unwrap_or(false);
+        is_marked && catalog.has(b"StructTreeRoot")
+    }
+
+    /// Removes the `OpenAction` of the document catalog, so viewers do nothing when the document
+    /// is opened. Unlike `strip_javascript` this also removes actions that are not scripts.
+    pub fn clear_open_action(&mut self) {
+        if let Ok(catalog_id) = self.doc.trailer.get(b"Root").and_then(Object::as_reference) {
+            if let Some(Ok(catalog)) = self.doc.objects.get_mut(&catalog_id).map(Object::as_dict_mut) {
+                catalog.remove(b"OpenAction");
+            }
+        }
     }
 
     fn get_form_name(string_u8: Vec<u8>) -> Result<String, LoadError> {
@@ -214,12 +1000,27 @@ impl Form {
         self.form_fields.len()
     }
 
+    /// Returns true if the form has no fillable fields
+    pub fn is_empty(&self) -> bool {
+        self.form_fields.is_empty()
+    }
+
+    /// Returns the number of fields the form has, same as `len`
+    pub fn field_count(&self) -> usize {
+        self.len()
+    }
+
+    /// Returns the number of objects in the document, a cheap measure of its size
+    pub fn object_count(&self) -> usize {
+        self.doc.objects.len()
+    }
+
     /// Gets the type of field of the given index
     ///
     /// # Panics
     /// This function will panic if the index is greater than the number of fields
-    pub fn get_type(&self, name: &String) -> Result<FieldType, LoadError> {
-        let field_id = self.form_fields.get(name.as_str()).ok_or(LoadError::DictionaryKeyNotFound)?;
+    pub fn get_type(&self, name: &str) -> Result<FieldType, LoadError> {
+        let field_id = self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
         let field = self.doc.objects.get(&field_id).unwrap().as_dict().unwrap();
         let obj_zero = Object::Integer(0);
         let type_str = field.get(b"FT").unwrap().as_name_str().unwrap();
@@ -244,115 +1045,92 @@ impl Form {
         }
     }
 
+    /// Gets the type of a field as a stable lowercase string for logs and exports: "text",
+    /// "checkbox", "radio", "listbox", "combobox", "button" or "signature". Unlike `get_type`, this
+    /// tells signature fields apart from text fields.
+    pub fn field_type_str(&self, name: &str) -> Result<&'static str, LoadError> {
+        let field_type = self.get_type(name)?;
+        let field = self.get_field_by_name(name.to_owned());
+        if field.get(b"FT").and_then(Object::as_name_str).ok() == Some("Sig") {
+            return Ok("signature");
+        }
+        Ok(match field_type {
+            FieldType::Text => "text",
+            FieldType::CheckBox => "checkbox",
+            FieldType::Radio => "radio",
+            FieldType::ListBox => "listbox",
+            FieldType::ComboBox => "combobox",
+            FieldType::Button => "button",
+        })
+    }
+
     /// Gets the types of all of the fields in the form
     pub fn get_all_types(&self) -> Vec<FieldType> {
         self.form_fields.keys().cloned().map(|f| self.get_type(&f).unwrap()).collect::<Vec<FieldType>>()
     }
 
-    /// Gets the state of field of the given index
+    /// Gets the state of field of the given index. Names are resolved like in `fill`, so the
+    /// index suffix may be left out and full names are accepted as well.
     ///
     /// # Panics
     /// This function will panic if the index is greater than the number of fields
-    pub fn get_state(&self, name: &String) -> FieldState {
+    pub fn get_state(&self, name: &str) -> FieldState {
+        let name = &self.resolve_name(name);
         let field_id = self.form_fields.get(name).unwrap();
         let field = self.doc.objects.get(&field_id).unwrap().as_dict().unwrap();
         match self.get_type(name).unwrap() {
-            FieldType::Button => FieldState::Button,
-            FieldType::Radio => FieldState::Radio {
-                selected: match field.get(b"V") {
+            FieldType::Button => FieldState::Button {
+                // Without an activation action, the action on releasing the mouse counts
+                action: match field.get(b"A") {
+                    Ok(action) => Some(action),
+                    Err(_) => field.get(b"AA").ok()
+                        .and_then(|aa| self.resolve_object(aa).ok())
+                        .and_then(|aa| aa.as_dict().ok())
+                        .and_then(|aa| aa.get(b"U").ok())
+                }.and_then(|action| self.parse_action(action)),
+            },
+            FieldType::Radio => {
+                let field_id = &self.get_value_owner(*field_id);
+                let field = self.doc.objects.get(field_id).unwrap().as_dict().unwrap();
+                let state = match field.get(b"V") {
                     Ok(name) => name.as_name_str().unwrap().to_owned(),
                     Err(_) => match field.get(b"AS") {
                         Ok(name) => name.as_name_str().unwrap().to_owned(),
                         Err(_) => "".to_owned(),
                     },
-                },
-                options: self.get_possibilities(field_id.clone()),
+                };
+                let states = self.get_radio_states(*field_id);
+                FieldState::Radio {
+                    selected: match states.iter().find(|(s, _)| *s == state) {
+                        Some((_, option)) => option.clone(),
+                        None => state
+                    },
+                    options: states.into_iter().map(|(_, option)| option).collect(),
+                }
             },
             FieldType::CheckBox => FieldState::CheckBox {
-                is_checked:
-                match field.get(b"V") {
-                    Ok(name) => if name.as_name_str().unwrap() == "Yes" { true } else { false },
-                    Err(_) => match field.get(b"AS") {
-                        Ok(name) => if name.as_name_str().unwrap() == "Yes" { true } else { false },
-                        Err(_) => false
+                is_checked: {
+                    let off_state = self.get_off_state(*field_id);
+                    match self.doc.objects.get(&self.get_value_owner(*field_id)).unwrap().as_dict().unwrap().get(b"V") {
+                        Ok(name) => name.as_name_str().unwrap() != off_state,
+                        Err(_) => match field.get(b"AS") {
+                            Ok(name) => name.as_name_str().unwrap() != off_state,
+                            Err(_) => false
+                        }
                     }
                 }
             },
             FieldType::ListBox => FieldState::ListBox {
-                // V field in a list box can be either text for one option, an array for many
-                // options, or null
-                selected: match field.get(b"V") {
-                    Ok(selection) => match selection {
-                        &Object::String(ref s, StringFormat::Literal) => vec![str::from_utf8(&s).unwrap().to_owned()],
-                        &Object::Array(ref chosen) => {
-                            let mut res = Vec::new();
-                            for obj in chosen {
-                                if let &Object::String(ref s, StringFormat::Literal) = obj {
-                                    res.push(str::from_utf8(&s).unwrap().to_owned());
-                                }
-                            }
-                            res
-                        }
-                        _ => Vec::new()
-                    },
-                    Err(_) => Vec::new(),
-                },
-                // The options is an array of either text elements or arrays where the second
-                // element is what we want
-                options: match field.get(b"Opt") {
-                    Ok(&Object::Array(ref options)) => options.iter().map(|x| {
-                        match x {
-                            &Object::String(ref s, StringFormat::Literal) => str::from_utf8(&s).unwrap().to_owned(),
-                            &Object::Array(ref arr) => if let &Object::String(ref s, StringFormat::Literal) = &arr[1] {
-                                str::from_utf8(&s).unwrap().to_owned()
-                            } else {
-                                String::new()
-                            },
-                            _ => String::new()
-                        }
-                    }).filter(|x| x.len() > 0).collect(),
-                    _ => Vec::new()
-                },
+                selected: self.parse_selected(field),
+                options: self.parse_options(field),
                 multiselect: {
                     let flags = ChoiceFlags::from_bits_truncate(field.get(b"Ff").unwrap().as_i64().unwrap() as u32);
                     flags.intersects(ChoiceFlags::MULTISELECT)
                 },
             },
             FieldType::ComboBox => FieldState::ComboBox {
-                // V field in a list box can be either text for one option, an array for many
-                // options, or null
-                selected: match field.get(b"V") {
-                    Ok(selection) => match selection {
-                        &Object::String(ref s, StringFormat::Literal) => vec![str::from_utf8(&s).unwrap().to_owned()],
-                        &Object::Array(ref chosen) => {
-                            let mut res = Vec::new();
-                            for obj in chosen {
-                                if let &Object::String(ref s, StringFormat::Literal) = obj {
-                                    res.push(str::from_utf8(&s).unwrap().to_owned());
-                                }
-                            }
-                            res
-                        }
-                        _ => Vec::new()
-                    },
-                    Err(_) => Vec::new(),
-                },
-                // The options is an array of either text elements or arrays where the second
-                // element is what we want
-                options: match field.get(b"Opt") {
-                    Ok(&Object::Array(ref options)) => options.iter().map(|x| {
-                        match x {
-                            &Object::String(ref s, StringFormat::Literal) => str::from_utf8(&s).unwrap().to_owned(),
-                            &Object::Array(ref arr) => if let &Object::String(ref s, StringFormat::Literal) = &arr[1] {
-                                str::from_utf8(&s).unwrap().to_owned()
-                            } else {
-                                String::new()
-                            },
-                            _ => String::new()
-                        }
-                    }).filter(|x| x.len() > 0).collect(),
-                    _ => Vec::new()
-                },
+                selected: self.parse_selected(field),
+                options: self.parse_options(field),
                 multiselect: {
                     let flags = ChoiceFlags::from_bits_truncate(field.get(b"Ff").unwrap().as_i64().unwrap() as u32);
                     flags.intersects(ChoiceFlags::MULTISELECT)
@@ -360,76 +1138,539 @@ impl Form {
             },
             FieldType::Text => FieldState::Text {
                 text:
-                match field.get(b"V") {
-                    Ok(&Object::String(ref s, StringFormat::Literal)) =>
-                        str::from_utf8(&s.clone()).unwrap().to_owned(),
+                // The value may be shared with or inherited from a parent
+                match self.get_inherited(self.get_value_owner(*field_id), b"V") {
+                    // PDF separates lines with carriage returns, callers expect line feeds
+                    Some(Object::String(s, StringFormat::Literal)) =>
+                        decode_value_string(s).replace("\r\n", "\n").replace('\r', "\n"),
+                    // Long texts may be stored in a stream
+                    Some(Object::Stream(stream)) => {
+                        let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+                        decode_value_string(&content).replace("\r\n", "\n").replace('\r', "\n")
+                    }
                     _ => "".to_owned()
                 }
             }
         }
     }
 
-    fn get_possibilities(&self, oid: ObjectId) -> Vec<String> {
+    /// Lists the push buttons that submit the form together with the URL they send the data to,
+    /// sorted by field name
+    pub fn submit_urls(&self) -> Vec<(String, String)> {
+        let mut res: Vec<(String, String)> = self.form_fields.keys()
+            .filter_map(|name| match self.get_type(name) {
+                Ok(FieldType::Button) => match self.get_state(name) {
+                    FieldState::Button { action: Some(ButtonAction::SubmitForm { url }) } => Some((name.clone(), url)),
+                    _ => None
+                },
+                _ => None
+            })
+            .collect();
+        res.sort();
+        res
+    }
+
+    /// Lists the JavaScript that viewers run on events of a field, as pairs of the trigger and the
+    /// script. Triggers are the keys of the additional actions (`AA`) of the field and its widgets,
+    /// e.g. `K` when a key is pressed, `F` to format, `V` to validate and `C` to calculate the
+    /// value.
+    pub fn field_scripts(&self, name: &str) -> Result<Vec<(String, String)>, LoadError> {
+        let field_id = *self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let mut dict_ids = vec![field_id];
+        dict_ids.extend(self.get_widget_ids(field_id).into_iter().filter(|widget_id| *widget_id != field_id));
+
         let mut res = Vec::new();
-        let kids_obj = self.doc.objects.get(&oid).unwrap().as_dict().unwrap().get(b"Kids");
-        if let Ok(&Object::Array(ref kids)) = kids_obj {
-            for kid in kids {
-                let options_dict = kid.deref(&self.doc).unwrap().as_dict().unwrap().get(b"AP").unwrap().as_dict().unwrap().get(b"N").unwrap().as_dict().unwrap();
-                for (key, _val) in options_dict {
-                    res.push(String::from_utf8(key.to_owned()).unwrap());
+        for dict_id in dict_ids {
+            let dict = self.doc.objects.get(&dict_id).ok_or(LoadError::NoSuchReference(dict_id))?.as_dict()?;
+            let additional_actions = match dict.get(b"AA") {
+                Ok(&Object::Reference(oid)) => self.doc.objects.get(&oid).ok_or(LoadError::NoSuchReference(oid))?,
+                Ok(additional_actions) => additional_actions,
+                Err(_) => continue
+            }.as_dict()?;
+            for (trigger, action) in additional_actions.iter() {
+                if let Some(ButtonAction::JavaScript { script }) = self.parse_action(action) {
+                    res.push((String::from_utf8_lossy(trigger).into_owned(), script));
                 }
             }
         }
-        res
+        Ok(res)
     }
 
-    /// If the field at index `n` is a text field, fills in that field with the text `s`.
-    /// If it is not a text field, returns ValueError
-    ///
-    /// # Panics
-    /// Will panic if n is larger than the number of fields
-    pub fn set_text(&mut self, name: &String, s: String) -> Result<(), ValueError> {
-        match self.get_type(name) {
-            Ok(FieldType::Text) => {
-                let field = self.doc.objects.get_mut(&self.form_fields[name]).unwrap().as_dict_mut().unwrap();
-                field.set("V", Object::String(s.into_bytes(), StringFormat::Literal));
-                field.remove(b"AP");
-                Ok(())
-            }
-            _ => Err(ValueError::TypeMismatch)
+    /// Guesses the kind of value a text field expects from the Acrobat function its format script
+    /// calls. Returns None if the field has no format script or a custom one.
+    pub fn inferred_format(&self, name: &str) -> Option<FieldFormat> {
+        let (_, script) = self.field_scripts(name).ok()?.into_iter().find(|(trigger, _)| trigger == "F")?;
+        if let Some(start) = script.find("AFNumber_Format(") {
+            // The fifth argument is the currency symbol
+            let args = &script[start + "AFNumber_Format(".len()..];
+            let currency = args.split(',').nth(4).map(str::trim).unwrap_or("");
+            return Some(if currency.is_empty() || currency == "\"\"" || currency == "''" {
+                FieldFormat::Number
+            } else {
+                FieldFormat::Currency
+            });
         }
+        let formats = [
+            ("AFPercent_Format", FieldFormat::Percent),
+            ("AFDate_Format", FieldFormat::Date),
+            ("AFTime_Format", FieldFormat::Time),
+            ("AFSpecial_Format", FieldFormat::Special),
+        ];
+        formats.iter().find(|(function, _)| script.contains(function)).map(|(_, format)| format.clone())
     }
 
-    /// If the field at index `n` is a radio field, toggles the radio button based on the value
-    /// `choice`
-    /// If it is not a radio button field or the choice is not a valid option, returns ValueError
-    ///
-    /// # Panics
-    /// Will panic if n is larger than the number of fields
-    pub fn set_radio(&mut self, name: &String, choice: String) -> Result<(), ValueError> {
-        let field_id = self.form_fields.get(name).unwrap();
-
-        match self.get_state(name) {
-            FieldState::Radio { selected: _, options } => if options.contains(&choice) {
-                let mut doc_objects = self.doc.objects.clone();
-                let field = doc_objects.get_mut(field_id).unwrap().as_dict_mut().unwrap();
-
-                let kids = field.get_mut(b"Kids").unwrap().as_array_mut().unwrap();
-                for kid in kids {
-                    let kid_reference = self.doc.objects.get_mut(&kid.as_reference().unwrap()).unwrap();
-                    let kid_dict = kid_reference.as_dict_mut().unwrap();
-                    let kid_options_dict = kid_dict.get_mut(b"AP").unwrap().as_dict_mut().unwrap().get_mut(b"N").unwrap().as_dict_mut().unwrap();
-                    if kid_options_dict.has(&choice.as_bytes()) {
-                        kid_dict.set("AS", Object::Name(choice.clone().into_bytes()));
-                    } else {
-                        kid_dict.set("AS", Object::Name(String::from("Off").into_bytes()));
+    /// Reads an action dictionary. Returns None for actions that are not modeled by `ButtonAction`.
+    fn parse_action(&self, action: &Object) -> Option<ButtonAction> {
+        let action = match action {
+            &Object::Reference(oid) => self.doc.objects.get(&oid)?,
+            action => action
+        }.as_dict().ok()?;
+        match action.get(b"S").and_then(Object::as_name_str).ok()? {
+            "SubmitForm" => {
+                // The URL is a file specification, either a plain string or a dictionary
+                let file_spec = match action.get(b"F").ok()? {
+                    &Object::Reference(oid) => self.doc.objects.get(&oid)?,
+                    file_spec => file_spec
+                };
+                let url = match file_spec {
+                    &Object::String(ref s, _) => decode_text_string(s),
+                    &Object::Dictionary(ref dict) => match dict.get(b"UF").or_else(|_| dict.get(b"F")) {
+                        Ok(&Object::String(ref s, _)) => decode_text_string(s),
+                        _ => return None
+                    },
+                    _ => return None
+                };
+                Some(ButtonAction::SubmitForm { url })
+            }
+            "ResetForm" => {
+                // Fields are listed by reference or by their full name
+                let fields = match action.get(b"Fields") {
+                    Ok(fields) => self.resolve_object(fields).ok()?.as_array().ok()?.iter()
+                        .filter_map(|field| match *field {
+                            Object::Reference(oid) => self.field_name_for_object(oid),
+                            Object::String(ref s, _) => Some(decode_text_string(s)),
+                            _ => None
+                        })
+                        .collect(),
+                    Err(_) => Vec::new()
+                };
+                let flags = action.get(b"Flags").and_then(Object::as_i64).unwrap_or(0);
+                Some(ButtonAction::ResetForm { fields, exclude: flags & 1 != 0 })
+            }
+            "JavaScript" => {
+                let script = match action.get(b"JS").ok()? {
+                    &Object::Reference(oid) => self.doc.objects.get(&oid)?,
+                    script => script
+                };
+                let script = match script {
+                    &Object::String(ref s, _) => decode_text_string(s),
+                    &Object::Stream(ref stream) => {
+                        let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+                        String::from_utf8_lossy(&content).into_owned()
                     }
+                    _ => return None
+                };
+                Some(ButtonAction::JavaScript { script })
+            }
+            _ => None
+        }
+    }
+
+    /// Reads the selected options of a choice field
+    fn parse_selected(&self, field: &Dictionary) -> Vec<String> {
+        // V field in a list box can be either text for one option, an array for many
+        // options, or null
+        match field.get(b"V") {
+            Ok(selection) => match selection {
+                &Object::String(ref s, StringFormat::Literal) => vec![decode_value_string(s)],
+                &Object::Array(ref chosen) => {
+                    let mut res = Vec::new();
+                    for obj in chosen {
+                        if let &Object::String(ref s, StringFormat::Literal) = obj {
+                            res.push(decode_value_string(s));
+                        }
+                    }
+                    res
+                }
+                _ => Vec::new()
+            },
+            // Some list boxes only store the indices of the selected options
+            Err(_) => match field.get(b"I").and_then(Object::as_array) {
+                Ok(indices) => {
+                    let options = self.parse_opt(field);
+                    indices.iter()
+                        .filter_map(|index| index.as_i64().ok())
+                        .filter_map(|index| options.get(index as usize).cloned())
+                        .collect()
+                }
+                Err(_) => Vec::new()
+            },
+        }
+    }
+
+    /// Reads all options of a choice field. The complete `Opt` array is always used, independent
+    /// of the window of options that `TI` scrolls to. Empty options are left out.
+    fn parse_options(&self, field: &Dictionary) -> Vec<String> {
+        self.parse_opt(field).into_iter().filter(|x| !x.is_empty()).collect()
+    }
+
+    /// Reads the entries of the `Opt` array of a choice field, including empty ones, so that
+    /// positions match the indices in `I`
+    fn parse_opt(&self, field: &Dictionary) -> Vec<String> {
+        let options = match field.get(b"Opt") {
+            Ok(&Object::Reference(oid)) => self.doc.objects.get(&oid),
+            Ok(obj) => Some(obj),
+            Err(_) => None
+        };
+        // The options is an array of either text elements or arrays where the second
+        // element is what we want
+        match options {
+            Some(&Object::Array(ref options)) => options.iter().map(|x| {
+                match x {
+                    &Object::String(ref s, _) => decode_value_string(s),
+                    &Object::Array(ref arr) => if let Some(&Object::String(ref s, _)) = arr.get(1) {
+                        decode_value_string(s)
+                    } else {
+                        String::new()
+                    },
+                    _ => String::new()
+                }
+            }).collect(),
+            _ => Vec::new()
+        }
+    }
+
+    /// Gets the options of a list or combo box. If `honor_sort` is set and the field has the `Sort`
+    /// flag, the options are sorted alphabetically the way viewers present them, otherwise they
+    /// are in document order.
+    pub fn get_options(&self, name: &str, honor_sort: bool) -> Result<Vec<String>, LoadError> {
+        match self.get_type(name)? {
+            FieldType::ListBox | FieldType::ComboBox => {
+                let field = self.get_field_by_name(name.to_owned());
+                let mut options = self.parse_options(field);
+                let flags = ChoiceFlags::from_bits_truncate(field.get(b"Ff").and_then(Object::as_i64).unwrap_or(0) as u32);
+                if honor_sort && flags.intersects(ChoiceFlags::SORT) {
+                    options.sort_by_key(|option| option.to_lowercase());
+                }
+                Ok(options)
+            }
+            _ => Err(LoadError::UnexpectedType)
+        }
+    }
+
+    /// Gets everything needed to present a list or combo box in one call
+    pub fn get_choice_snapshot(&self, name: &str) -> Result<ChoiceSnapshot, LoadError> {
+        match self.get_type(name)? {
+            FieldType::ListBox | FieldType::ComboBox => {
+                let field = self.get_field_by_name(name.to_owned());
+                let flags = ChoiceFlags::from_bits_truncate(field.get(b"Ff").and_then(Object::as_i64).unwrap_or(0) as u32);
+                Ok(ChoiceSnapshot {
+                    options: self.parse_options(field),
+                    selected: self.parse_selected(field),
+                    multiselect: flags.intersects(ChoiceFlags::MULTISELECT),
+                    editable: flags.intersects(ChoiceFlags::EDIT),
+                })
+            }
+            _ => Err(LoadError::UnexpectedType)
+        }
+    }
+
+    /// Gets the index of the first option visible in a scrollable list box (`TI`), 0 if unset
+    pub fn list_top_index(&self, name: &str) -> Result<usize, LoadError> {
+        match self.get_type(name)? {
+            FieldType::ListBox | FieldType::ComboBox => {
+                let field = self.get_field_by_name(name.to_owned());
+                Ok(field.get(b"TI").and_then(Object::as_i64).unwrap_or(0) as usize)
+            }
+            _ => Err(LoadError::UnexpectedType)
+        }
+    }
+
+    /// Counts the options of a choice or radio field, which is the length of the options returned by
+    /// `get_state`. The options of choice fields are counted without decoding them.
+    pub fn options_len(&self, name: &str) -> Result<usize, LoadError> {
+        let field_id = *self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        match self.get_type(name)? {
+            FieldType::ListBox | FieldType::ComboBox => {
+                let field = self.get_field_by_name(name.to_owned());
+                let options = match field.get(b"Opt") {
+                    Ok(&Object::Reference(oid)) => self.doc.objects.get(&oid),
+                    Ok(obj) => Some(obj),
+                    Err(_) => None
+                };
+                // Count the options `parse_options` keeps
+                Ok(match options.map(Object::as_array) {
+                    Some(Ok(options)) => options.iter().filter(|option| match option.as_array() {
+                        Ok(pair) => matches!(pair.get(1).map(Object::as_str), Some(Ok(s)) if !s.is_empty()),
+                        Err(_) => matches!(option.as_str(), Ok(s) if !s.is_empty())
+                    }).count(),
+                    _ => 0
+                })
+            }
+            FieldType::Radio => Ok(self.get_radio_states(field_id).len()),
+            _ => Err(LoadError::UnexpectedType)
+        }
+    }
+
+    /// Pairs the appearance states of the widgets of a radio field with the option each one
+    /// stands for. Usually both are the same, but a field with `Opt` names the states after the
+    /// index of the option in `Opt`, and widgets without any `AP/N` state are matched to `Opt` by
+    /// their position.
+    fn get_radio_states(&self, oid: ObjectId) -> Vec<(String, String)> {
+        let field = match self.doc.objects.get(&oid).map(Object::as_dict) {
+            Some(Ok(field)) => field,
+            _ => return Vec::new()
+        };
+        let opt: Vec<Option<String>> = match field.get(b"Opt") {
+            Ok(&Object::Reference(opt_id)) => self.doc.objects.get(&opt_id),
+            Ok(opt) => Some(opt),
+            Err(_) => None
+        }.and_then(|opt| opt.as_array().ok()).map(|opt| opt.iter().map(|option| match option {
+            &Object::String(ref s, _) => Some(decode_text_string(s)),
+            _ => None
+        }).collect()).unwrap_or_default();
+        let option_for = |state: &str| state.parse::<usize>().ok()
+            .and_then(|index| opt.get(index).cloned().flatten())
+            .unwrap_or_else(|| state.to_owned());
+
+        // A radio without kids is its own single widget
+        let mut res = Vec::new();
+        for (index, widget_id) in self.get_descendant_widget_ids(oid).into_iter().enumerate() {
+            let states = self.get_normal_states(widget_id);
+            if states.iter().any(|state| state != "Off") {
+                res.extend(states.into_iter().map(|state| {
+                    let option = if state == "Off" { state.clone() } else { option_for(&state) };
+                    (state, option)
+                }));
+            } else if let Some(Some(option)) = opt.get(index) {
+                res.push((index.to_string(), option.clone()));
+            }
+        }
+        res
+    }
+
+    /// Gets the display labels of the options of a radio field, paired with the export name that
+    /// `set_radio` expects. The label is read from each widget's `TU` entry or, failing that, from
+    /// its `MK` caption. Widgets without a label are left out.
+    pub fn radio_labels(&self, name: &str) -> Result<Vec<(String, String)>, LoadError> {
+        let field_id = self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        match self.get_type(name)? {
+            FieldType::Radio => {}
+            _ => return Err(LoadError::UnexpectedType)
+        }
+
+        let mut res = Vec::new();
+        let field = self.doc.objects.get(field_id).ok_or(LoadError::NoSuchReference(*field_id))?.as_dict()?;
+        if let Some(kids) = get_kids(&self.doc, field) {
+            for kid in kids {
+                let kid_dict = kid.deref(&self.doc)?.as_dict()?;
+                let export = match kid_dict.get(b"AP").and_then(Object::as_dict).and_then(|ap| ap.get(b"N")).and_then(Object::as_dict) {
+                    Ok(options_dict) => options_dict.iter()
+                        .map(|(key, _)| String::from_utf8_lossy(key).into_owned())
+                        .find(|key| key != "Off"),
+                    Err(_) => None
+                };
+                let label = match kid_dict.get(b"TU") {
+                    Ok(&Object::String(ref s, _)) => Some(decode_text_string(s)),
+                    _ => match kid_dict.get(b"MK").and_then(Object::as_dict).and_then(|mk| mk.get(b"CA")) {
+                        Ok(&Object::String(ref s, _)) => Some(decode_text_string(s)),
+                        _ => None
+                    }
+                };
+                if let (Some(label), Some(export)) = (label, export) {
+                    res.push((label, export));
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Selects the option of the radio field whose display label (see `radio_labels`) is `label`.
+    /// If it is not a radio button field or no option carries the label, returns ValueError
+    pub fn set_radio_by_label(&mut self, name: &str, label: &str) -> Result<(), ValueError> {
+        let labels = self.radio_labels(name).or(Err(ValueError::TypeMismatch))?;
+        match labels.into_iter().find(|(l, _)| l == label) {
+            Some((_, export)) => self.set_radio(name, export),
+            None => Err(ValueError::InvalidSelection)
+        }
+    }
+
+    /// Gets the text of a text field. Line breaks are returned as `\n`.
+    pub fn get_text(&self, name: &str) -> Result<String, LoadError> {
+        match self.get_type(name)? {
+            FieldType::Text => match self.get_state(name) {
+                FieldState::Text { text } => Ok(text),
+                _ => Err(LoadError::UnexpectedType)
+            },
+            _ => Err(LoadError::UnexpectedType)
+        }
+    }
+
+    /// Gets the value of a field the way the PDF stores it: the export value of a checked check
+    /// box and an empty string for an unchecked one, the export value of the selected radio
+    /// button, the selected options of a choice field joined by "; ", or the text of a text field.
+    /// Push buttons have no value and return `LoadError::UnexpectedType`.
+    pub fn get_value_string(&self, name: &str) -> Result<String, LoadError> {
+        let field_id = *self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        match self.get_state(name) {
+            FieldState::Button { .. } => Err(LoadError::UnexpectedType),
+            FieldState::Text { text } => Ok(text),
+            FieldState::CheckBox { is_checked: false } => Ok(String::new()),
+            FieldState::CheckBox { is_checked: true } => {
+                let owner = self.doc.objects.get(&self.get_value_owner(field_id)).ok_or(LoadError::NoSuchReference(field_id))?.as_dict()?;
+                match owner.get(b"V").and_then(Object::as_name_str) {
+                    Ok(state) => Ok(state.to_owned()),
+                    // Checked by its widget's state alone
+                    Err(_) => Ok(self.get_on_state(field_id).unwrap_or_else(|| "Yes".to_owned()))
+                }
+            }
+            FieldState::Radio { selected, .. } => Ok(if selected == "Off" { String::new() } else { selected }),
+            FieldState::ListBox { selected, .. } | FieldState::ComboBox { selected, .. } => Ok(selected.join("; ")),
+        }
+    }
+
+    /// Counts the selected options of a radio or choice field. A radio field has at most one.
+    /// Other fields return `LoadError::UnexpectedType`.
+    pub fn count_selected(&self, name: &str) -> Result<usize, LoadError> {
+        self.get_type(name)?;
+        match self.get_state(name) {
+            FieldState::Radio { selected, .. } => Ok(if selected.is_empty() || selected == "Off" { 0 } else { 1 }),
+            FieldState::ListBox { selected, .. } | FieldState::ComboBox { selected, .. } => Ok(selected.len()),
+            _ => Err(LoadError::UnexpectedType)
+        }
+    }
+
+    /// Gets the names and values of all text fields, in the order the fields appear in the
+    /// document
+    pub fn text_field_values(&self) -> Vec<(String, String)> {
+        self.field_order.iter()
+            .filter_map(|name| Some((name.clone(), self.get_text(name).ok()?)))
+            .collect()
+    }
+
+    /// Gets the raw bytes of the field's `V` entry, or None if the value is not a string. Useful to
+    /// inspect the encoding of a value.
+    pub fn get_value_bytes(&self, name: &str) -> Result<Option<Vec<u8>>, LoadError> {
+        let field_id = self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let field = self.doc.objects.get(field_id).ok_or(LoadError::NoSuchReference(*field_id))?.as_dict()?;
+        match field.get(b"V") {
+            Ok(&Object::String(ref s, _)) => Ok(Some(s.clone())),
+            _ => Ok(None)
+        }
+    }
+
+    /// If the field at index `n` is a text field, fills in that field with the text `s`.
+    /// Line breaks in `s` are stored as `\r` in multiline fields.
+    /// If it is not a text field, returns ValueError
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_text(&mut self, name: &str, s: impl Into<String>) -> Result<(), ValueError> {
+        self.set_text_value(name, s.into(), false)
+    }
+
+    /// Like `set_text`, but leaves the existing appearance stream of the field intact. Viewers
+    /// keep showing the old text until the appearances are regenerated.
+    pub fn set_text_keep_appearance(&mut self, name: &str, s: impl Into<String>) -> Result<(), ValueError> {
+        self.set_text_value(name, s.into(), true)
+    }
+
+    /// Like `set_text`, but cuts the text to the maximum length (`MaxLen`) of the field, counted in
+    /// characters, instead of writing more than comb fields have cells for
+    pub fn set_text_truncating(&mut self, name: &str, s: impl Into<String>) -> Result<(), ValueError> {
+        let mut s = s.into();
+        if let Some(&field_id) = self.form_fields.get(name) {
+            if let Some(Ok(max_len)) = self.get_inherited(field_id, b"MaxLen").map(Object::as_i64) {
+                if let Some((end, _)) = s.char_indices().nth(max_len.max(0) as usize) {
+                    s.truncate(end);
+                }
+            }
+        }
+        self.set_text_value(name, s, false)
+    }
+
+    fn set_text_value(&mut self, name: &str, s: String, keep_appearance: bool) -> Result<(), ValueError> {
+        match self.get_type(name) {
+            Ok(FieldType::Text) => {
+                let field_id = self.form_fields[name];
+                let field = self.doc.objects.get(&field_id).unwrap().as_dict().unwrap();
+                let flags = TextFlags::from_bits_truncate(field.get(b"Ff").and_then(Object::as_i64).unwrap_or(0) as u32);
+                let s = if flags.intersects(TextFlags::MULTILINE) {
+                    s.replace("\r\n", "\r").replace('\n', "\r")
+                } else {
+                    s
+                };
+                let owner = self.get_value_owner(field_id);
+                self.set_owned_value(owner, Object::String(encode_pdf_text_string(&s), StringFormat::Literal));
+                if !keep_appearance && self.appearance_mode == AppearanceMode::Regenerate {
+                    let shown = if flags.intersects(TextFlags::PASSWORD) {
+                        "*".repeat(s.chars().count())
+                    } else {
+                        s.replace('\r', " ")
+                    };
+                    self.write_text_appearances(owner, &shown);
+                } else if !keep_appearance {
+                    // Every widget sharing the value shows the old text until regenerated
+                    let mut stale = vec![owner];
+                    stale.extend(self.get_descendant_fields(owner));
+                    stale.extend(self.get_descendant_widget_ids(owner));
+                    for oid in stale {
+                        if let Some(Ok(dict)) = self.doc.objects.get_mut(&oid).map(Object::as_dict_mut) {
+                            dict.remove(b"AP");
+                        }
+                    }
+                }
+                Ok(())
+            }
+            _ => Err(ValueError::TypeMismatch)
+        }
+    }
+
+    /// Like `set_text`, but returns the text the field held before
+    pub fn set_text_returning(&mut self, name: &str, s: impl Into<String>) -> Result<String, ValueError> {
+        let previous = self.get_text(name).map_err(|_| ValueError::TypeMismatch)?;
+        self.set_text(name, s)?;
+        Ok(previous)
+    }
+
+    /// If the field at index `n` is a radio field, toggles the radio button based on the value
+    /// `choice`
+    /// If it is not a radio button field or the choice is not a valid option, returns ValueError
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_radio(&mut self, name: &str, choice: String) -> Result<(), ValueError> {
+        let field_id = self.get_value_owner(*self.form_fields.get(name).unwrap());
+
+        match self.get_type(name) {
+            Ok(FieldType::Radio) => {
+                // The choice may be given by its option or by the appearance state standing for it
+                let mut states: Vec<String> = self.get_radio_states(field_id).into_iter()
+                    .filter(|(state, option)| *option == choice || *state == choice)
+                    .map(|(state, _)| state)
+                    .collect();
+                let state = match states.first() {
+                    Some(state) => state.clone(),
+                    None => return Err(ValueError::InvalidSelection)
+                };
+                // Widgets with the same option but different states, as with `Opt`, only turn on
+                // together in unison. Widgets sharing a state always do.
+                let flags = ButtonFlags::from_bits_truncate(self.doc.objects.get(&field_id).unwrap().as_dict().unwrap().get(b"Ff").and_then(Object::as_i64).unwrap_or(0) as u32);
+                if !flags.intersects(ButtonFlags::RADIO_IN_UNISON) {
+                    states.truncate(1);
+                }
+
+                for (index, kid_id) in self.get_descendant_widget_ids(field_id).into_iter().enumerate() {
+                    let kid_states = self.get_normal_states(kid_id);
+                    let kid_state = states.iter().find(|state| kid_states.contains(state)
+                        || (kid_states.iter().all(|s| s == "Off") && index.to_string() == **state));
+                    let kid_state = kid_state.map(String::as_str).unwrap_or("Off");
+                    let kid_dict = self.doc.objects.get_mut(&kid_id).unwrap().as_dict_mut().unwrap();
+                    kid_dict.set("AS", Object::Name(kid_state.as_bytes().to_vec()));
                 }
 
-                field.set("V", Object::Name(choice.into_bytes()));
+                self.set_owned_value(field_id, Object::Name(state.into_bytes()));
                 Ok(())
-            } else {
-                Err(ValueError::InvalidSelection)
             },
             _ => Err(ValueError::TypeMismatch)
         }
@@ -437,18 +1678,30 @@ impl Form {
 
 
     /// If the field at index `n` is a checkbox field, toggles the check box based on the value
-    /// `is_checked`.
+    /// `is_checked`. A checked box takes the on-state of its widgets, which is `Yes` unless the
+    /// widgets name it differently.
     /// If it is not a checkbox field, returns ValueError
     ///
     /// # Panics
     /// Will panic if n is larger than the number of fields
-    pub fn set_check_box(&mut self, name: &String, is_checked: bool) -> Result<(), ValueError> {
+    pub fn set_check_box(&mut self, name: &str, is_checked: bool) -> Result<(), ValueError> {
         match self.get_type(name) {
             Ok(FieldType::CheckBox) => {
-                let state = Object::Name({ if is_checked { "Yes" } else { "Off" } }.to_owned().into_bytes());
-                let field = self.doc.objects.get_mut(&self.form_fields.get(name).unwrap()).unwrap().as_dict_mut().unwrap();
-                field.set("V", state.clone());
-                field.set("AS", state);
+                let field_id = self.form_fields[name];
+                let off_state = self.get_off_state(field_id);
+                let state = if is_checked {
+                    self.get_on_state(field_id).unwrap_or_else(|| "Yes".to_owned())
+                } else {
+                    off_state.clone()
+                };
+                self.set_owned_value(self.get_value_owner(field_id), Object::Name(state.as_bytes().to_vec()));
+                for widget_id in self.get_widget_ids(field_id) {
+                    // Widgets of the same box may stand for different on-states
+                    let states = self.get_normal_states(widget_id);
+                    let widget_state = if states.is_empty() || states.contains(&state) { &state } else { &off_state };
+                    let widget = self.doc.objects.get_mut(&widget_id).unwrap().as_dict_mut().unwrap();
+                    widget.set("AS", Object::Name(widget_state.as_bytes().to_vec()));
+                }
                 Ok(())
             }
             _ => Err(ValueError::TypeMismatch)
@@ -456,18 +1709,406 @@ impl Form {
     }
 
 
+    /// Sets the value of the field `owner` and removes stale values from its named kids, so that
+    /// the value reads the same through the owner and its kids
+    fn set_owned_value(&mut self, owner: ObjectId, value: Object) {
+        for kid_id in self.get_descendant_fields(owner) {
+            if let Some(Ok(kid)) = self.doc.objects.get_mut(&kid_id).map(Object::as_dict_mut) {
+                kid.remove(b"V");
+            }
+        }
+        let field = self.doc.objects.get_mut(&owner).unwrap().as_dict_mut().unwrap();
+        field.set("V", value);
+    }
+
+    /// Gets the state a check box turns to when it is checked, which is the first appearance state
+    /// of its widgets other than the off-state (see `get_off_state`)
+    fn get_on_state(&self, field_id: ObjectId) -> Option<String> {
+        let off_state = self.get_off_state(field_id);
+        self.get_widget_ids(field_id).into_iter()
+            .flat_map(|widget_id| self.get_normal_states(widget_id))
+            .find(|state| *state != off_state)
+    }
+
+    /// Gets the state of an unchecked check box. This is `Off` unless the widgets have no such
+    /// appearance state and instead one whose normal appearance draws nothing, which a few forms
+    /// use as their off-state.
+    fn get_off_state(&self, field_id: ObjectId) -> String {
+        let widget_ids = self.get_widget_ids(field_id);
+        let states: Vec<(ObjectId, String)> = widget_ids.iter()
+            .flat_map(|widget_id| self.get_normal_states(*widget_id).into_iter().map(move |state| (*widget_id, state)))
+            .collect();
+        if states.len() < 2 || states.iter().any(|(_, state)| state == "Off") {
+            return "Off".to_owned();
+        }
+        states.into_iter()
+            .find(|(widget_id, state)| match self.get_widget_appearance_stream(*widget_id, Some(state)) {
+                Ok(Some(content)) => content.iter().all(u8::is_ascii_whitespace),
+                _ => false
+            })
+            .map(|(_, state)| state)
+            .unwrap_or_else(|| "Off".to_owned())
+    }
+
+    /// Gets the names of the normal appearances (the keys of `AP/N`) of a widget
+    fn get_normal_states(&self, widget_id: ObjectId) -> Vec<String> {
+        let normal = self.doc.objects.get(&widget_id)
+            .and_then(|widget| widget.as_dict().ok())
+            .and_then(|widget| widget.get(b"AP").and_then(Object::as_dict).ok())
+            .and_then(|ap| ap.get(b"N").ok())
+            .and_then(|normal| match normal {
+                &Object::Reference(oid) => self.doc.objects.get(&oid),
+                normal => Some(normal)
+            });
+        match normal {
+            Some(&Object::Dictionary(ref normal)) => normal.iter()
+                .map(|(key, _)| String::from_utf8_lossy(key).into_owned())
+                .collect(),
+            _ => Vec::new()
+        }
+    }
+
+    /// Gets the appearance states the widgets of a field support, which are the keys of their
+    /// normal (`AP/N`) and down (`AP/D`) appearances. These are the states `set_check_box_value`
+    /// and `set_radio` can select. The states are sorted and listed once even if several widgets
+    /// support them.
+    pub fn appearance_states(&self, name: &str) -> Result<Vec<String>, LoadError> {
+        let field_id = *self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let mut states: Vec<String> = Vec::new();
+        for widget_id in self.get_widget_ids(field_id) {
+            let ap = self.doc.objects.get(&widget_id)
+                .and_then(|widget| widget.as_dict().ok())
+                .and_then(|widget| widget.get(b"AP").and_then(Object::as_dict).ok());
+            for key in &[&b"N"[..], &b"D"[..]] {
+                let appearances = ap.and_then(|ap| ap.get(key).ok()).and_then(|appearances| match appearances {
+                    &Object::Reference(oid) => self.doc.objects.get(&oid),
+                    appearances => Some(appearances)
+                });
+                if let Some(Ok(appearances)) = appearances.map(Object::as_dict) {
+                    states.extend(appearances.iter().map(|(state, _)| String::from_utf8_lossy(state).into_owned()));
+                }
+            }
+        }
+        states.sort();
+        states.dedup();
+        Ok(states)
+    }
+
+    /// If the field is a checkbox field, checks the box with the export value `on_value`, which
+    /// must be one of the box's appearance states, or unchecks it if `on_value` is None.
+    /// If it is not a checkbox field or the box has no such state, returns ValueError
+    pub fn set_check_box_value(&mut self, name: &str, on_value: Option<&str>) -> Result<(), ValueError> {
+        let field_id = match self.get_type(name) {
+            Ok(FieldType::CheckBox) => self.form_fields[name],
+            _ => return Err(ValueError::TypeMismatch)
+        };
+        let widget_ids = self.get_widget_ids(field_id);
+        let off_state = self.get_off_state(field_id);
+        let state = on_value.unwrap_or(&off_state);
+        if on_value.is_some() && !widget_ids.iter().any(|id| self.get_normal_states(*id).iter().any(|s| s == state)) {
+            return Err(ValueError::InvalidSelection);
+        }
+
+        for widget_id in widget_ids {
+            let widget_state = if self.get_normal_states(widget_id).iter().any(|s| s == state) { state } else { &off_state };
+            let widget = self.doc.objects.get_mut(&widget_id).unwrap().as_dict_mut().unwrap();
+            widget.set("AS", Object::Name(widget_state.as_bytes().to_vec()));
+        }
+        let field = self.doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap();
+        field.set("V", Object::Name(state.as_bytes().to_vec()));
+        Ok(())
+    }
+
+    /// Checks a check box with the on-state `on_name`, for boxes whose on-state is not `Yes`. Same
+    /// as `set_check_box_value` with `Some(on_name)`.
+    pub fn set_check_box_on_name(&mut self, name: &str, on_name: &str) -> Result<(), ValueError> {
+        self.set_check_box_value(name, Some(on_name))
+    }
+
+    /// If the field is a checkbox field, checks it if it is unchecked and unchecks it otherwise.
+    /// Returns whether the box is checked afterwards.
+    /// If it is not a checkbox field, returns ValueError
+    pub fn toggle_check_box(&mut self, name: &str) -> Result<bool, ValueError> {
+        let field_id = match self.get_type(name) {
+            Ok(FieldType::CheckBox) => self.form_fields[name],
+            _ => return Err(ValueError::TypeMismatch)
+        };
+        let off_state = self.get_off_state(field_id);
+        let field = self.doc.objects.get(&field_id).unwrap().as_dict().unwrap();
+        let is_checked = match field.get(b"V").or_else(|_| field.get(b"AS")) {
+            Ok(&Object::Name(ref state)) => *state != off_state.as_bytes(),
+            _ => false
+        };
+        if is_checked {
+            self.set_check_box_value(name, None)?;
+        } else {
+            match self.get_on_state(field_id) {
+                Some(on_state) => self.set_check_box_value(name, Some(&on_state))?,
+                None => self.set_check_box(name, true)?
+            }
+        }
+        Ok(!is_checked)
+    }
+
+    /// Looks up an entry of a field that may be inherited from its parents, like `DV`
+    fn get_inherited(&self, field_id: ObjectId, key: &[u8]) -> Option<&Object> {
+        let mut current = Some(field_id);
+        while let Some(field_id) = current {
+            let field = self.doc.objects.get(&field_id)?.as_dict().ok()?;
+            if let Ok(value) = field.get(key) {
+                return match value {
+                    &Object::Reference(oid) => self.doc.objects.get(&oid),
+                    value => Some(value)
+                };
+            }
+            current = field.get(b"Parent").and_then(Object::as_reference).ok();
+        }
+        None
+    }
+
+    /// Gets the default appearance string of a field, which may be inherited from its parents or
+    /// the AcroForm
+    fn get_default_appearance(&self, field_id: ObjectId) -> Option<String> {
+        let da = match self.get_inherited(field_id, b"DA") {
+            Some(da) => da,
+            None => self.get_acroform().ok()?.get(b"DA").ok()?
+        };
+        da.as_str().ok().map(|da| String::from_utf8_lossy(da).into_owned())
+    }
+
+    /// Follows `obj` to the object it refers to if it is a reference
+    fn resolve_object<'a>(&'a self, obj: &'a Object) -> Result<&'a Object, LoadError> {
+        match *obj {
+            Object::Reference(oid) => self.doc.objects.get(&oid).ok_or(LoadError::NoSuchReference(oid)),
+            _ => Ok(obj)
+        }
+    }
+
+    /// Gets the decoded content of the normal appearance stream (`AP/N`) of the first widget of a
+    /// field. Check boxes and radio buttons have one stream per appearance state; `state` picks one
+    /// of them and defaults to the widget's current state (`AS`). Returns None if the widget has no
+    /// such appearance.
+    pub fn get_appearance_stream(&self, name: &str, state: Option<&str>) -> Result<Option<Vec<u8>>, LoadError> {
+        let field_id = *self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let widget_id = *self.get_widget_ids(field_id).first().ok_or(LoadError::DictionaryKeyNotFound)?;
+        self.get_widget_appearance_stream(widget_id, state)
+    }
+
+    /// Gets the decoded content of the normal appearance stream of a widget, see
+    /// `get_appearance_stream`
+    fn get_widget_appearance_stream(&self, widget_id: ObjectId, state: Option<&str>) -> Result<Option<Vec<u8>>, LoadError> {
+        let widget = self.doc.objects.get(&widget_id).ok_or(LoadError::NoSuchReference(widget_id))?.as_dict()?;
+        let normal = match widget.get(b"AP") {
+            Ok(ap) => match self.resolve_object(ap)?.as_dict()?.get(b"N") {
+                Ok(normal) => self.resolve_object(normal)?,
+                Err(_) => return Ok(None)
+            },
+            Err(_) => return Ok(None)
+        };
+        let stream = match *normal {
+            Object::Stream(ref stream) => stream,
+            Object::Dictionary(ref states) => {
+                let state = match state {
+                    Some(state) => state.as_bytes(),
+                    None => match widget.get(b"AS").and_then(Object::as_name) {
+                        Ok(state) => state,
+                        Err(_) => return Ok(None)
+                    }
+                };
+                match states.get(state) {
+                    Ok(stream) => self.resolve_object(stream)?.as_stream()?,
+                    Err(_) => return Ok(None)
+                }
+            }
+            _ => return Err(LoadError::UnexpectedType)
+        };
+        Ok(Some(stream.decompressed_content().unwrap_or_else(|_| stream.content.clone())))
+    }
+
+    /// Gets the background and border colors and the rotation of the first widget of a field
+    pub fn get_widget_appearance(&self, name: &str) -> Result<WidgetAppearance, LoadError> {
+        let field_id = *self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let widget_id = *self.get_widget_ids(field_id).first().ok_or(LoadError::DictionaryKeyNotFound)?;
+        let widget = self.doc.objects.get(&widget_id).ok_or(LoadError::NoSuchReference(widget_id))?.as_dict()?;
+        let mk = match widget.get(b"MK") {
+            Ok(&Object::Reference(oid)) => self.doc.objects.get(&oid).ok_or(LoadError::NoSuchReference(oid))?.as_dict()?,
+            Ok(mk) => mk.as_dict()?,
+            Err(_) => return Ok(WidgetAppearance { background: None, border: None, rotation: 0 })
+        };
+        let color = |key: &[u8]| mk.get(key).and_then(Object::as_array).ok().and_then(|c| color_from_array(c));
+        Ok(WidgetAppearance {
+            background: color(b"BG"),
+            border: color(b"BC"),
+            rotation: mk.get(b"R").and_then(Object::as_i64).unwrap_or(0),
+        })
+    }
+
+    /// Gets the color of the text of a field, as set in its default appearance (`DA`). Text is
+    /// black if the default appearance sets no color.
+    pub fn get_text_color(&self, name: &str) -> Result<Color, LoadError> {
+        let field_id = *self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let da = self.get_default_appearance(field_id).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let tokens: Vec<&str> = da.split_whitespace().collect();
+        Ok(parse_da_color(&tokens).map(|(color, _)| color).unwrap_or(Color::Gray(0.0)))
+    }
+
+    /// Sets the color of the text of a field by rewriting the color of its default appearance
+    /// (`DA`). The font and size are kept. The field gets a `DA` of its own if it inherits it.
+    pub fn set_text_color(&mut self, name: &str, color: Color) -> Result<(), ValueError> {
+        let field_id = *self.form_fields.get(name).ok_or(ValueError::TypeMismatch)?;
+        let da = self.get_default_appearance(field_id).unwrap_or_default();
+        let mut tokens: Vec<&str> = da.split_whitespace().collect();
+        if let Some((_, range)) = parse_da_color(&tokens) {
+            tokens.drain(range);
+        }
+        let color = match color {
+            Color::Gray(gray) => format!("{} g", gray),
+            Color::Rgb(r, g, b) => format!("{} {} {} rg", r, g, b),
+            Color::Cmyk(c, m, y, k) => format!("{} {} {} {} k", c, m, y, k),
+        };
+        tokens.push(&color);
+
+        let field = self.doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap();
+        field.set("DA", Object::string_literal(tokens.join(" ")));
+        field.remove(b"AP");
+        Ok(())
+    }
+
+    /// Gets the default value of a field, which `reset` restores. The default may be inherited
+    /// from a parent field. Check boxes and radios name their default state, choice fields may
+    /// have several default options. Returns an empty list if the field has no default.
+    pub fn get_default(&self, name: &str) -> Result<Vec<String>, LoadError> {
+        let field_id = *self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let to_string = |value: &Object| match *value {
+            Object::String(ref s, _) => Some(decode_value_string(s)),
+            Object::Name(ref name) => Some(String::from_utf8_lossy(name).into_owned()),
+            _ => None
+        };
+        Ok(match self.get_inherited(field_id, b"DV") {
+            Some(&Object::Array(ref values)) => values.iter().filter_map(to_string).collect(),
+            Some(value) => to_string(value).into_iter().collect(),
+            None => Vec::new()
+        })
+    }
+
+    /// Resets a field to its default value (see `get_default`). A field without a default is
+    /// cleared. The appearances of text fields and combo boxes are regenerated or dropped as the
+    /// `AppearanceMode` says, like the setters do.
+    pub fn reset(&mut self, name: &str) -> Result<(), ValueError> {
+        let field_id = *self.form_fields.get(name).ok_or(ValueError::TypeMismatch)?;
+        let field_type = self.get_type(name).map_err(|_| ValueError::TypeMismatch)?;
+        let default = self.get_inherited(field_id, b"DV").cloned();
+        let off_state = self.get_off_state(field_id);
+
+        let field = self.doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap();
+        match default {
+            Some(ref default) => field.set("V", default.clone()),
+            None => { field.remove(b"V"); }
+        }
+        match field_type {
+            FieldType::CheckBox | FieldType::Radio => {
+                let state = match default {
+                    Some(Object::Name(ref state)) => String::from_utf8_lossy(state).into_owned(),
+                    _ => off_state.clone()
+                };
+                for widget_id in self.get_widget_ids(field_id) {
+                    let widget_state = if self.get_normal_states(widget_id).contains(&state) { &state } else { &off_state };
+                    let widget = self.doc.objects.get_mut(&widget_id).unwrap().as_dict_mut().unwrap();
+                    widget.set("AS", Object::Name(widget_state.as_bytes().to_vec()));
+                }
+            }
+            FieldType::Text | FieldType::ComboBox | FieldType::ListBox => {
+                field.remove(b"I");
+                let flags = TextFlags::from_bits_truncate(field.get(b"Ff").and_then(Object::as_i64).unwrap_or(0) as u32);
+                if field_type != FieldType::ListBox && self.appearance_mode == AppearanceMode::Regenerate {
+                    let text = self.get_default(name).ok().and_then(|default| default.into_iter().next()).unwrap_or_default();
+                    let shown = if field_type == FieldType::Text && flags.intersects(TextFlags::PASSWORD) {
+                        "*".repeat(text.chars().count())
+                    } else {
+                        text.replace('\r', " ")
+                    };
+                    self.write_text_appearances(field_id, &shown);
+                } else {
+                    let mut stale = vec![field_id];
+                    stale.extend(self.get_descendant_widget_ids(field_id));
+                    for oid in stale {
+                        if let Some(Ok(dict)) = self.doc.objects.get_mut(&oid).map(Object::as_dict_mut) {
+                            dict.remove(b"AP");
+                        }
+                    }
+                }
+            }
+            FieldType::Button => {}
+        }
+        Ok(())
+    }
+
+    /// Sets the appearance state (`AS`) of the widgets of every checkbox and radio field to match
+    /// the field's value, for forms that set `V` but leave viewers showing the old state
+    pub fn repair_appearance_states(&mut self) {
+        let field_ids: Vec<ObjectId> = self.form_fields.iter()
+            .filter(|(name, _)| matches!(self.get_type(name), Ok(FieldType::CheckBox) | Ok(FieldType::Radio)))
+            .map(|(_, field_id)| *field_id)
+            .collect();
+
+        for field_id in field_ids {
+            let value = match self.doc.objects.get(&field_id).and_then(|field| field.as_dict().ok()).map(|field| field.get(b"V")) {
+                Some(Ok(&Object::Name(ref value))) => String::from_utf8_lossy(value).into_owned(),
+                _ => continue
+            };
+            let off_state = self.get_off_state(field_id);
+            for widget_id in self.get_widget_ids(field_id) {
+                let state = if self.get_normal_states(widget_id).contains(&value) { &value } else { &off_state };
+                let widget = self.doc.objects.get_mut(&widget_id).unwrap().as_dict_mut().unwrap();
+                widget.set("AS", Object::Name(state.as_bytes().to_vec()));
+            }
+        }
+    }
+
+    /// Repairs common defects of forms in one pass. Which repairs are applied is chosen by
+    /// `options`.
+    pub fn normalize(&mut self, options: NormalizeOptions) {
+        if options.sync_appearance_states {
+            self.repair_appearance_states();
+        }
+        if options.need_appearances {
+            if let Ok(acroform) = self.get_acroform_mut() {
+                acroform.set("NeedAppearances", true);
+            }
+        }
+        if options.print_widgets {
+            let widget_ids: Vec<ObjectId> = self.form_fields.values()
+                .flat_map(|field_id| self.get_widget_ids(*field_id))
+                .collect();
+            for widget_id in widget_ids {
+                if let Some(Ok(widget)) = self.doc.objects.get_mut(&widget_id).map(Object::as_dict_mut) {
+                    let flags = widget.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+                    // Bit 3 of the annotation flags is Print
+                    widget.set("F", flags | 4);
+                }
+            }
+        }
+        if options.drop_xfa {
+            self.drop_xfa();
+        }
+    }
+
     /// If the field at index `n` is a listbox or comboox field, selects the options in `choice`
     /// If it is not a listbox or combobox field or one of the choices is not a valid option, or if too many choices are selected, returns ValueError
     ///
     /// # Panics
     /// Will panic if n is larger than the number of fields
-    pub fn set_choice(&mut self, name: &String, choices: Vec<String>) -> Result<(), ValueError> {
-        let field_id = self.form_fields.get(name).unwrap();
-        match self.get_state(name) {
+    pub fn set_choice(&mut self, name: &str, choices: Vec<String>) -> Result<(), ValueError> {
+        let field_id = *self.form_fields.get(name).unwrap();
+        let state = self.get_state(name);
+        let is_combo = matches!(state, FieldState::ComboBox { .. });
+        match state {
             FieldState::ListBox { selected: _, options, multiselect } | FieldState::ComboBox { selected: _, options, multiselect } => if choices.iter().fold(true, |a, h| options.contains(h) && a) {
                 if !multiselect && choices.len() > 1 {
                     Err(ValueError::TooManySelected)
                 } else {
+                    // `I` counts the empty entries of `Opt` that `options` leaves out
+                    let opt = self.parse_opt(self.doc.objects.get(&field_id).unwrap().as_dict().unwrap());
                     let field = self.doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap();
                     match choices.len() {
                         0 => field.set("V", Object::Null),
@@ -475,6 +2116,36 @@ impl Form {
                                                            StringFormat::Literal)),
                         _ => field.set("V", Object::Array(choices.iter().map(|x| Object::String(x.clone().into_bytes(), StringFormat::Literal)).collect()))
                     };
+                    // Some viewers select the options of list boxes by their index in `I`. Indices
+                    // left over from an earlier selection would keep showing it, in combo boxes too,
+                    // which select by their value alone.
+                    let indices: Vec<Object> = opt.iter().enumerate()
+                        .filter(|(_, option)| choices.contains(option))
+                        .map(|(index, _)| Object::Integer(index as i64))
+                        .collect();
+                    if is_combo || indices.is_empty() {
+                        field.remove(b"I");
+                    } else {
+                        field.set("I", indices);
+                    }
+                    // A combo box displays its value, so its old appearance would show the previous
+                    // selection. Combo boxes that commit on change, or all of them when appearances
+                    // are regenerated, show the new selection right away; the appearance of others
+                    // is dropped for the viewer to regenerate.
+                    let flags = ChoiceFlags::from_bits_truncate(field.get(b"Ff").and_then(Object::as_i64).unwrap_or(0) as u32);
+                    if is_combo && (flags.contains(ChoiceFlags::COMMIT_ON_CHANGE) || self.appearance_mode == AppearanceMode::Regenerate) {
+                        let text = choices.first().cloned().unwrap_or_default();
+                        self.write_text_appearances(field_id, &text);
+                    } else if is_combo {
+                        for widget_id in self.get_widget_ids(field_id) {
+                            if let Some(Ok(widget)) = self.doc.objects.get_mut(&widget_id).map(Object::as_dict_mut) {
+                                widget.remove(b"AP");
+                            }
+                        }
+                        if let Ok(acroform) = self.get_acroform_mut() {
+                            acroform.set("NeedAppearances", true);
+                        }
+                    }
                     Ok(())
                 }
             } else {
@@ -484,69 +2155,3217 @@ impl Form {
         }
     }
 
-    pub fn get_field_by_name(&self, name: String) -> &Dictionary {
-        self.doc.objects.get(self.form_fields.get(name.as_str()).unwrap()).unwrap().as_dict().unwrap()
+    /// Like `set_choice`, but matches the choices against the options ignoring case and selects
+    /// the options as the PDF spells them. An option that matches exactly is preferred.
+    pub fn set_choice_fuzzy(&mut self, name: &str, choices: Vec<String>) -> Result<(), ValueError> {
+        if !self.form_fields.contains_key(name) {
+            return Err(ValueError::TypeMismatch);
+        }
+        let options = match self.get_state(name) {
+            FieldState::ListBox { options, .. } | FieldState::ComboBox { options, .. } => options,
+            _ => return Err(ValueError::TypeMismatch)
+        };
+        let choices = choices.into_iter()
+            .map(|choice| {
+                let folded = choice.to_lowercase();
+                options.iter()
+                    .find(|option| **option == choice)
+                    .or_else(|| options.iter().find(|option| option.to_lowercase() == folded))
+                    .cloned()
+                    .ok_or(ValueError::InvalidSelection)
+            })
+            .collect::<Result<Vec<String>, ValueError>>()?;
+        self.set_choice(name, choices)
     }
 
-    pub fn get_field_names(&self) -> Vec<String> {
-        self.form_fields.keys().cloned().collect::<Vec<String>>()
+    /// Like `set_choice`, but selects the options by their 0-based position in the field's `Opt`
+    /// array, counting empty options too. An index past the last option returns
+    /// `ValueError::InvalidSelection`.
+    pub fn set_choice_by_index(&mut self, name: &str, indices: Vec<usize>) -> Result<(), ValueError> {
+        let options = match self.get_type(name) {
+            Ok(FieldType::ListBox) | Ok(FieldType::ComboBox) => self.parse_opt(self.get_field_by_name(name.to_owned())),
+            _ => return Err(ValueError::TypeMismatch)
+        };
+        let choices = indices.into_iter()
+            .map(|index| options.get(index).cloned().ok_or(ValueError::InvalidSelection))
+            .collect::<Result<Vec<String>, ValueError>>()?;
+        self.set_choice(name, choices)
     }
 
-    /// Fills the formula
-    pub fn fill(&mut self, fields: HashMap<String, String>) -> Result<(), FieldError> {
-        for (k, value) in fields {
-            let key = if self.form_fields.contains_key(&k) {
-                k
-            } else {
-                let mut new_name = k.clone();
-                new_name.push_str("[0]");
-                new_name
+    /// Gives every widget of a text field or combo box, including the widgets of named kids that
+    /// share its value, an appearance that shows `text` in the field's default appearance
+    fn write_text_appearances(&mut self, field_id: ObjectId, text: &str) {
+        let da = self.get_default_appearance(field_id).unwrap_or_else(|| "/Helv 0 Tf 0 g".to_owned());
+        let resources = self.get_acroform().ok().and_then(|acroform| acroform.get(b"DR").ok()).cloned();
+        for widget_id in self.get_descendant_widget_ids(field_id) {
+            let [x1, y1, x2, y2] = match self.get_widget_rect(widget_id) {
+                Some(rect) => rect,
+                None => continue
             };
+            let (width, height) = ((x2 - x1).abs(), (y2 - y1).abs());
+            let mut dict = Dictionary::new();
+            dict.set("Type", "XObject");
+            dict.set("Subtype", "Form");
+            dict.set("BBox", vec![0.into(), 0.into(), Object::Real(width as _), Object::Real(height as _)]);
+            if let Some(ref resources) = resources {
+                dict.set("Resources", resources.clone());
+            }
+            let stream_id = self.doc.add_object(Stream::new(dict, single_line_appearance(&da, text, width, height)));
+            let mut ap = Dictionary::new();
+            ap.set("N", stream_id);
+            let widget = self.doc.objects.get_mut(&widget_id).unwrap().as_dict_mut().unwrap();
+            widget.set("AP", ap);
+        }
+    }
 
-            let map_v = value.clone();
-            let map_err = |x: ValueError| FieldError::new(x, key.clone(), map_v);
+    pub fn get_field_by_name(&self, name: String) -> &Dictionary {
+        self.doc.objects.get(self.form_fields.get(name.as_str()).unwrap()).unwrap().as_dict().unwrap()
+    }
 
-            match self.get_type(&key) {
-                Ok(FieldType::Radio) => {
-                    self.set_radio(&key, value).map_err(map_err)?;
-                }
-                Ok(FieldType::CheckBox) => {
-                    self.set_check_box(&key, value.to_lowercase().eq("true")).map_err(map_err)?;
-                }
-                Ok(FieldType::Text) => {
-                    self.set_text(&key, value).map_err(map_err)?;
-                }
-                _ => {}
-            };
+    /// Converts an object to JSON for `field_debug_json`. References are resolved while `depth`
+    /// is above zero and shown as "n g R" otherwise.
+    fn object_to_debug_json(&self, obj: &Object, depth: usize) -> serde_json::Value {
+        use serde_json::Value;
+        match *obj {
+            Object::Null => Value::Null,
+            Object::Boolean(b) => Value::Bool(b),
+            Object::Integer(i) => Value::from(i),
+            Object::Real(_) => as_number(obj).map(Value::from).unwrap_or(Value::Null),
+            Object::Name(ref name) => Value::String(format!("/{}", String::from_utf8_lossy(name))),
+            Object::String(ref s, _) => Value::String(decode_value_string(s)),
+            Object::Array(ref array) => Value::Array(array.iter().map(|item| self.object_to_debug_json(item, depth)).collect()),
+            Object::Dictionary(ref dict) => Value::Object(dict.iter()
+                .map(|(key, value)| (String::from_utf8_lossy(key).into_owned(), self.object_to_debug_json(value, depth)))
+                .collect()),
+            Object::Stream(ref stream) => {
+                let mut res = serde_json::Map::new();
+                res.insert("dict".to_owned(), self.object_to_debug_json(&Object::Dictionary(stream.dict.clone()), depth));
+                res.insert("content_length".to_owned(), Value::from(stream.content.len()));
+                Value::Object(res)
+            }
+            Object::Reference((id, generation)) => match self.doc.objects.get(&(id, generation)) {
+                Some(target) if depth > 0 => self.object_to_debug_json(target, depth - 1),
+                _ => Value::String(format!("{} {} R", id, generation))
+            },
         }
+    }
 
-        Ok(())
+    /// Renders the dictionary of a field as pretty printed JSON for debugging. Objects the field
+    /// refers to are included, references within them are shown as "n g R". Names keep their
+    /// leading slash to tell them apart from strings.
+    pub fn field_debug_json(&self, name: &str) -> Result<String, LoadError> {
+        let field_id = *self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let field = self.doc.objects.get(&field_id).ok_or(LoadError::NoSuchReference(field_id))?;
+        Ok(serde_json::to_string_pretty(&self.object_to_debug_json(field, 1)).unwrap())
     }
 
+    /// Gets the `ObjectId` of the dictionary of the field with the given name
+    pub fn get_field_id(&self, name: &str) -> Option<ObjectId> {
+        self.form_fields.get(name).cloned()
+    }
 
-    /// Saves the form to the specified path
-    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<(), io::Error> {
-        self.doc.save(path).map(|_| ())
+    /// Renders the `ObjectId` of every field as JSON object of the form
+    /// `{"name": [object number, generation]}`, for tools that edit the document directly
+    pub fn field_map_json(&self) -> String {
+        let map: serde_json::Map<String, serde_json::Value> = self.form_fields.iter()
+            .map(|(name, &(number, generation))| (name.clone(), serde_json::json!([number, generation])))
+            .collect();
+        serde_json::Value::Object(map).to_string()
+    }
+
+    /// Finds the field that owns the object `oid`, which is either the field dictionary itself or
+    /// one of its widget annotations
+    pub fn field_name_for_object(&self, oid: ObjectId) -> Option<String> {
+        self.form_fields.iter().find(|(_, field_id)| {
+            if **field_id == oid {
+                return true;
+            }
+            match self.doc.objects.get(field_id).and_then(|field| field.as_dict().ok()).and_then(|field| get_kids(&self.doc, field)) {
+                Some(kids) => kids.iter().any(|kid| kid.as_reference().ok() == Some(oid)),
+                None => false
+            }
+        }).map(|(name, _)| name.clone())
+    }
+
+    /// Gets the widget annotations of a field. These are either the widget kids of the field or,
+    /// if the field and its only widget share one dictionary, the field itself.
+    fn get_widget_ids(&self, field_id: ObjectId) -> Vec<ObjectId> {
+        let field = match self.doc.objects.get(&field_id).map(Object::as_dict) {
+            Some(Ok(field)) => field,
+            _ => return Vec::new()
+        };
+        match get_kids(&self.doc, field) {
+            Some(kids) => kids.iter()
+                .filter_map(|kid| kid.as_reference().ok())
+                .filter(|kid_id| match self.doc.objects.get(kid_id).map(Object::as_dict) {
+                    Some(Ok(kid)) => !kid.has(b"T"),
+                    _ => false
+                })
+                .collect(),
+            None => vec![field_id]
+        }
+    }
+
+    /// Gets the field that holds the value of the field `field_id`. In hierarchical forms a parent
+    /// may have the same field type as its kids, in which case the value belongs to the topmost
+    /// such parent.
+    fn get_value_owner(&self, field_id: ObjectId) -> ObjectId {
+        let field_type = |oid: &ObjectId| self.doc.objects.get(oid)
+            .and_then(|field| field.as_dict().ok())
+            .and_then(|field| field.get(b"FT").and_then(Object::as_name).ok());
+        let own_type = field_type(&field_id);
+        let mut owner = field_id;
+        while let Some(parent_id) = self.doc.objects.get(&owner)
+            .and_then(|field| field.as_dict().ok())
+            .and_then(|field| field.get(b"Parent").and_then(Object::as_reference).ok()) {
+            if own_type.is_none() || field_type(&parent_id) != own_type {
+                break;
+            }
+            owner = parent_id;
+        }
+        owner
+    }
+
+    /// Gets the named kids of a field and all of their named kids
+    fn get_descendant_fields(&self, field_id: ObjectId) -> Vec<ObjectId> {
+        let kids = match self.doc.objects.get(&field_id).map(Object::as_dict) {
+            Some(Ok(field)) => match get_kids(&self.doc, field) {
+                Some(kids) => kids.iter().filter_map(|kid| kid.as_reference().ok()).collect(),
+                None => Vec::new()
+            },
+            _ => Vec::new()
+        };
+        let mut res = Vec::new();
+        for kid_id in kids {
+            if matches!(self.doc.objects.get(&kid_id).map(Object::as_dict), Some(Ok(kid)) if kid.has(b"T")) {
+                res.push(kid_id);
+                res.extend(self.get_descendant_fields(kid_id));
+            }
+        }
+        res
+    }
+
+    /// Gets the widgets of a field and of all of its named kids
+    fn get_descendant_widget_ids(&self, field_id: ObjectId) -> Vec<ObjectId> {
+        let mut res = self.get_widget_ids(field_id);
+        for kid_id in self.get_descendant_fields(field_id) {
+            res.extend(self.get_widget_ids(kid_id));
+        }
+        res
+    }
+
+    /// Gets the 0-based index of the page a widget annotation is placed on, either from its `P`
+    /// entry or by searching the `Annots` of all pages
+    fn get_widget_page(&self, widget_id: ObjectId) -> Option<usize> {
+        let pages = self.doc.get_pages();
+        let widget = self.doc.objects.get(&widget_id)?.as_dict().ok()?;
+        if let Ok(&Object::Reference(page_id)) = widget.get(b"P") {
+            if let Some(index) = pages.values().position(|id| *id == page_id) {
+                return Some(index);
+            }
+        }
+        pages.values().position(|page_id| {
+            let annots = self.doc.objects.get(page_id)
+                .and_then(|page| page.as_dict().ok())
+                .and_then(|page| page.get(b"Annots").ok())
+                .and_then(|annots| match annots {
+                    &Object::Reference(oid) => self.doc.objects.get(&oid),
+                    annots => Some(annots)
+                });
+            match annots {
+                Some(&Object::Array(ref annots)) => annots.iter().any(|annot| annot.as_reference().ok() == Some(widget_id)),
+                _ => false
+            }
+        })
+    }
+
+    /// Counts the pages that hold at least one widget of a form field
+    pub fn pages_with_fields(&self) -> usize {
+        let mut pages: Vec<usize> = self.form_fields.values()
+            .flat_map(|field_id| self.get_widget_ids(*field_id))
+            .filter_map(|widget_id| self.get_widget_page(widget_id))
+            .collect();
+        pages.sort_unstable();
+        pages.dedup();
+        pages.len()
+    }
+
+    /// Gets the effective `Rotate` of the page the field is placed on in degrees, taking the value
+    /// inherited from the page tree into account. Fields without a page are reported as unrotated.
+    pub fn get_field_page_rotation(&self, name: &str) -> Result<i64, LoadError> {
+        let field_id = *self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let page_id = match self.get_widget_ids(field_id).into_iter().filter_map(|id| self.get_widget_page(id)).next() {
+            Some(page) => match self.doc.get_pages().values().nth(page) {
+                Some(page_id) => *page_id,
+                None => return Ok(0)
+            },
+            None => return Ok(0)
+        };
+
+        let mut node = self.doc.objects.get(&page_id).ok_or(LoadError::NoSuchReference(page_id))?.as_dict()?;
+        loop {
+            if let Ok(rotate) = node.get(b"Rotate") {
+                return Ok(self.resolve_object(rotate)?.as_i64()?.rem_euclid(360));
+            }
+            node = match node.get(b"Parent") {
+                Ok(&Object::Reference(parent_id)) => self.doc.objects.get(&parent_id).ok_or(LoadError::NoSuchReference(parent_id))?.as_dict()?,
+                _ => return Ok(0)
+            };
+        }
+    }
+
+    /// Collects the entries of a name or number tree, like the ones of `EmbeddedFiles` and
+    /// `PageLabels`. `key` is the array holding the entries of a node, `Names` or `Nums`, and
+    /// `parse_key` reads the key of an entry; entries with an unreadable key are skipped.
+    fn collect_tree<'a, K, F>(&'a self, node: &'a Object, key: &[u8], parse_key: &F, entries: &mut Vec<(K, &'a Object)>)
+        where F: Fn(&Object) -> Option<K>
+    {
+        let node = match node {
+            &Object::Reference(oid) => match self.doc.objects.get(&oid) {
+                Some(node) => node,
+                None => return
+            },
+            node => node
+        };
+        let node = match node.as_dict() {
+            Ok(node) => node,
+            Err(_) => return
+        };
+        if let Ok(&Object::Array(ref pairs)) = node.get(key) {
+            for pair in pairs.chunks(2) {
+                if let (Some(key), Some(value)) = (parse_key(&pair[0]), pair.get(1)) {
+                    entries.push((key, value));
+                }
+            }
+        }
+        if let Ok(&Object::Array(ref kids)) = node.get(b"Kids") {
+            for kid in kids {
+                self.collect_tree(kid, key, parse_key, entries);
+            }
+        }
+    }
+
+    /// Gets the names of the files attached to the document
+    pub fn embedded_file_names(&self) -> Vec<String> {
+        let embedded_files = self.doc.trailer.get(b"Root").ok()
+            .and_then(|catalog| catalog.deref(&self.doc).ok())
+            .and_then(|catalog| catalog.as_dict().ok())
+            .and_then(|catalog| catalog.get(b"Names").ok())
+            .and_then(|names| match names {
+                &Object::Reference(oid) => self.doc.objects.get(&oid),
+                names => Some(names)
+            })
+            .and_then(|names| names.as_dict().ok())
+            .and_then(|names| names.get(b"EmbeddedFiles").ok());
+
+        let mut entries = Vec::new();
+        if let Some(embedded_files) = embedded_files {
+            self.collect_tree(embedded_files, b"Names", &|key| key.as_str().ok().map(decode_text_string), &mut entries);
+        }
+        entries.into_iter().map(|(name, _)| name).collect()
+    }
+
+    /// Gets the label of the page a field is placed on, e.g. "iii" or "A-1", as defined by the
+    /// document's page labels. Returns None if the document has no page labels.
+    pub fn get_field_page_label(&self, name: &str) -> Result<Option<String>, LoadError> {
+        let field_id = *self.form_fields.get(name).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let page = match self.get_widget_ids(field_id).into_iter().filter_map(|id| self.get_widget_page(id)).next() {
+            Some(page) => page as i64,
+            None => return Ok(None)
+        };
+        let catalog = self.doc.trailer.get(b"Root")
+            .or(Err(LoadError::DictionaryKeyNotFound))?
+            .deref(&self.doc)?
+            .as_dict()?;
+        let page_labels = match catalog.get(b"PageLabels") {
+            Ok(page_labels) => page_labels,
+            Err(_) => return Ok(None)
+        };
+
+        let mut ranges = Vec::new();
+        self.collect_tree(page_labels, b"Nums", &|key| key.as_i64().ok(), &mut ranges);
+        ranges.sort_by_key(|(start, _)| *start);
+        let (start, label) = match ranges.into_iter().rev().find(|(start, _)| *start <= page) {
+            Some(range) => range,
+            None => return Ok(None)
+        };
+        let label = match label {
+            &Object::Reference(oid) => self.doc.objects.get(&oid).ok_or(LoadError::NoSuchReference(oid))?,
+            label => label
+        }.as_dict()?;
+
+        let mut res = match label.get(b"P") {
+            Ok(&Object::String(ref prefix, _)) => decode_text_string(prefix),
+            _ => String::new()
+        };
+        if let Ok(style) = label.get(b"S").and_then(Object::as_name_str) {
+            let first = label.get(b"St").and_then(Object::as_i64).unwrap_or(1);
+            res.push_str(&format_page_number(first + page - start, style));
+        }
+        Ok(Some(res))
+    }
+
+    /// Copies a field to a new, independent field named `new_name`, shown at `rect` on the page
+    /// with the index `page`. The copy has the type, flags and look of the original but no
+    /// value. Only the first widget of a field shown in several places is copied.
+    pub fn clone_field(&mut self, src: &str, new_name: &str, page: usize, rect: [f64; 4]) -> Result<(), LoadError> {
+        if self.form_fields.contains_key(new_name) {
+            return Err(LoadError::DuplicateName(new_name.to_owned()));
+        }
+        let field_id = *self.form_fields.get(src).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let field_type = self.get_type(src)?;
+        let page_id = *self.doc.get_pages().get(&(page as u32 + 1)).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let widget_id = *self.get_widget_ids(field_id).first().ok_or(LoadError::DictionaryKeyNotFound)?;
+
+        // Merge the field and its widget into one dictionary, like a field shown in one place
+        let mut clone = self.doc.objects.get(&widget_id).ok_or(LoadError::NoSuchReference(widget_id))?.as_dict()?.clone();
+        for (key, value) in self.doc.objects.get(&field_id).ok_or(LoadError::NoSuchReference(field_id))?.as_dict()?.iter() {
+            clone.set(key.clone(), value.clone());
+        }
+        for key in &["Kids", "Parent", "V", "I", "AA"] {
+            clone.remove(key.as_bytes());
+        }
+        if clone.has(b"AS") {
+            clone.set("AS", "Off");
+        }
+        // The appearance of a text or choice field shows its value
+        if let FieldType::Text | FieldType::ComboBox | FieldType::ListBox = field_type {
+            clone.remove(b"AP");
+        }
+        clone.set("Rect", rect.iter().map(|coord| Object::Real(*coord)).collect::<Vec<Object>>());
+        self.add_widget_field(clone, new_name, page_id)
+    }
+
+    /// Adds a text field named `name`, shown at `rect` on the page with the index `page`. The
+    /// field is empty and its text is sized to fit.
+    pub fn add_text_field(&mut self, name: &str, page: usize, rect: [f64; 4]) -> Result<(), LoadError> {
+        if self.form_fields.contains_key(name) {
+            return Err(LoadError::DuplicateName(name.to_owned()));
+        }
+        let page_id = *self.doc.get_pages().get(&(page as u32 + 1)).ok_or(LoadError::DictionaryKeyNotFound)?;
+        let mut field = Dictionary::new();
+        field.set("Type", "Annot");
+        field.set("Subtype", "Widget");
+        field.set("FT", "Tx");
+        // Printable
+        field.set("F", 4);
+        field.set("DA", Object::string_literal("/Helv 0 Tf 0 g"));
+        field.set("Rect", rect.iter().map(|coord| Object::Real(*coord)).collect::<Vec<Object>>());
+        self.add_widget_field(field, name, page_id)
+    }
+
+    /// Adds a field that is its own widget to the page `page_id` and the AcroForm under `name`
+    fn add_widget_field(&mut self, mut field: Dictionary, name: &str, page_id: ObjectId) -> Result<(), LoadError> {
+        field.set("T", encode_field_name(name));
+        field.set("P", page_id);
+        let field_id = self.doc.add_object(field);
+
+        let page = self.doc.objects.get_mut(&page_id).ok_or(LoadError::NoSuchReference(page_id))?.as_dict_mut()?;
+        let annots_id = match page.get(b"Annots") {
+            Ok(&Object::Reference(annots_id)) => Some(annots_id),
+            Ok(_) => None,
+            Err(_) => {
+                page.set("Annots", Vec::<Object>::new());
+                None
+            }
+        };
+        match annots_id {
+            Some(annots_id) => self.doc.objects.get_mut(&annots_id).ok_or(LoadError::NoSuchReference(annots_id))?,
+            None => page.get_mut(b"Annots")?
+        }.as_array_mut()?.push(field_id.into());
+        self.get_acroform_mut()?.get_mut(b"Fields")?.as_array_mut()?.push(field_id.into());
+
+        self.form_fields.insert(name.to_owned(), field_id);
+        self.field_order.push(name.to_owned());
+        Ok(())
+    }
+
+    /// Renames a field. Fails with `DuplicateName` if another field already has the new name.
+    pub fn rename_field(&mut self, name: &str, new_name: &str) -> Result<(), LoadError> {
+        let mut mapping = HashMap::new();
+        mapping.insert(name.to_owned(), new_name.to_owned());
+        self.rename_fields(&mapping)
+    }
+
+    /// Renames several fields at once, mapping old names to new ones. Nothing is renamed if an
+    /// old name is unknown or a new name is taken by a field that keeps its name or by another
+    /// renamed field, so fields may also swap their names.
+    pub fn rename_fields(&mut self, mapping: &HashMap<String, String>) -> Result<(), LoadError> {
+        let mut new_names = Vec::with_capacity(mapping.len());
+        for (name, new_name) in mapping {
+            if !self.form_fields.contains_key(name) {
+                return Err(LoadError::DictionaryKeyNotFound);
+            }
+            let taken = self.form_fields.contains_key(new_name) && !mapping.contains_key(new_name);
+            if taken || new_names.contains(&new_name) {
+                return Err(LoadError::DuplicateName(new_name.clone()));
+            }
+            new_names.push(new_name);
+        }
+
+        let renamed: Vec<(ObjectId, &String)> = mapping.iter()
+            .map(|(name, new_name)| (self.form_fields[name], new_name))
+            .collect();
+        for &(field_id, new_name) in &renamed {
+            self.doc.objects.get_mut(&field_id)
+                .ok_or(LoadError::NoSuchReference(field_id))?
+                .as_dict_mut()?
+                .set("T", encode_field_name(new_name));
+        }
+        for name in mapping.keys() {
+            self.form_fields.remove(name);
+        }
+        for (field_id, new_name) in renamed {
+            self.form_fields.insert(new_name.clone(), field_id);
+        }
+        for name in self.field_order.iter_mut() {
+            if let Some(new_name) = mapping.get(name) {
+                *name = new_name.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the page index and rectangle (`[x1, y1, x2, y2]`) of every widget of a field. A field
+    /// shown in several places has one widget per place, each with its own appearance.
+    pub fn widget_rects(&self, name: &str) -> Vec<(usize, [f64; 4])> {
+        let field_id = match self.form_fields.get(name) {
+            Some(field_id) => *field_id,
+            None => return Vec::new()
+        };
+        self.get_widget_ids(field_id).into_iter().filter_map(|widget_id| {
+            Some((self.get_widget_page(widget_id)?, self.get_widget_rect(widget_id)?))
+        }).collect()
+    }
+
+    /// Gets the rectangle (`[x1, y1, x2, y2]`) of a widget annotation
+    fn get_widget_rect(&self, widget_id: ObjectId) -> Option<[f64; 4]> {
+        let widget = self.doc.objects.get(&widget_id)?.as_dict().ok()?;
+        let rect = widget.get(b"Rect").ok()?.as_array().ok()?;
+        if rect.len() != 4 {
+            return None;
+        }
+        let mut coords = [0.0; 4];
+        for (coord, obj) in coords.iter_mut().zip(rect) {
+            *coord = as_number(obj)?;
+        }
+        Some(coords)
+    }
+
+    /// Gets the ids of the annotations listed in the `Annots` of a page
+    fn get_page_annotations(&self, page: &Dictionary) -> Vec<ObjectId> {
+        let annots = match page.get(b"Annots") {
+            Ok(&Object::Reference(oid)) => self.doc.objects.get(&oid),
+            Ok(annots) => Some(annots),
+            Err(_) => None
+        };
+        match annots {
+            Some(&Object::Array(ref annots)) => annots.iter().filter_map(|annot| annot.as_reference().ok()).collect(),
+            _ => Vec::new()
+        }
+    }
+
+    /// Lists the annotations that are not widgets of a form field, like comments, highlights or
+    /// stamps, as the page index, the id and the subtype of each annotation
+    pub fn other_annotations(&self) -> Vec<(usize, ObjectId, String)> {
+        let mut res = Vec::new();
+        for (index, page_id) in self.doc.get_pages().values().enumerate() {
+            let page = match self.doc.objects.get(page_id).map(Object::as_dict) {
+                Some(Ok(page)) => page,
+                _ => continue
+            };
+            for annot_id in self.get_page_annotations(page) {
+                let subtype = match self.doc.objects.get(&annot_id).map(Object::as_dict) {
+                    Some(Ok(annot)) => annot.get(b"Subtype").and_then(Object::as_name_str).unwrap_or("").to_owned(),
+                    _ => continue
+                };
+                if subtype == "Widget" && self.field_name_for_object(annot_id).is_some() {
+                    continue;
+                }
+                res.push((index, annot_id, subtype));
+            }
+        }
+        res
+    }
+
+    /// Gets the names of all fields in the order a viewer tabs through them. Pages are visited in
+    /// order. Within a page, widgets are ordered by rows (`/Tabs /R`), by columns (`/Tabs /C`) or
+    /// else as listed in the page's `Annots`. A field with several widgets is listed once, at its
+    /// first widget. Fields without a widget on any page are not listed.
+    pub fn tab_order(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for page_id in self.doc.get_pages().values() {
+            let page = match self.doc.objects.get(page_id).map(Object::as_dict) {
+                Some(Ok(page)) => page,
+                _ => continue
+            };
+            let mut widgets: Vec<(ObjectId, String)> = self.get_page_annotations(page).into_iter()
+                .filter_map(|annot_id| Some((annot_id, self.field_name_for_object(annot_id)?)))
+                .collect();
+            let tabs = page.get(b"Tabs").and_then(Object::as_name).ok();
+            if tabs == Some(b"R") || tabs == Some(b"C") {
+                let by_rows = tabs == Some(b"R");
+                // Sort by the top left corner: rows run top to bottom, columns left to right
+                widgets.sort_by(|(a, _), (b, _)| {
+                    let key = |oid: &ObjectId| match self.get_widget_rect(*oid) {
+                        Some([x1, y1, x2, y2]) => (-y1.max(y2), x1.min(x2)),
+                        None => (0.0, 0.0)
+                    };
+                    let (a_top, a_left) = key(a);
+                    let (b_top, b_left) = key(b);
+                    let order = if by_rows {
+                        (a_top, a_left).partial_cmp(&(b_top, b_left))
+                    } else {
+                        (a_left, a_top).partial_cmp(&(b_left, b_top))
+                    };
+                    order.unwrap_or(std::cmp::Ordering::Equal)
+                });
+            }
+            for (_, name) in widgets {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    pub fn get_field_names(&self) -> Vec<String> {
+        self.form_fields.keys().cloned().collect::<Vec<String>>()
+    }
+
+    /// Like `get_field_names`, but sorted lexicographically for deterministic output
+    pub fn get_field_names_sorted(&self) -> Vec<String> {
+        let mut names = self.get_field_names();
+        names.sort();
+        names
+    }
+
+    /// Gets the names of all barcode fields, in the order the fields appear in the document.
+    /// Barcode fields are text fields whose value is drawn as a barcode; they are marked by a
+    /// `BarcodeType` entry, or the `PMD` dictionary of Acrobat, on the field or one of its widgets.
+    pub fn barcode_fields(&self) -> Vec<String> {
+        let is_barcode = |oid: &ObjectId| match self.doc.objects.get(oid).map(Object::as_dict) {
+            Some(Ok(dict)) => dict.has(b"BarcodeType") || dict.has(b"PMD"),
+            _ => false
+        };
+        self.field_order.iter()
+            .filter(|name| {
+                let field_id = self.form_fields[name.as_str()];
+                is_barcode(&field_id) || self.get_widget_ids(field_id).iter().any(is_barcode)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Gets the names of all fields for which `pred` returns true when called with the field's
+    /// name and type, in the order the fields appear in the document
+    pub fn fields_matching<F: Fn(&str, FieldType) -> bool>(&self, pred: F) -> Vec<String> {
+        self.field_order.iter()
+            .filter(|name| match self.get_type(name) {
+                Ok(field_type) => pred(name, field_type),
+                Err(_) => false
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Gets the names of all fields whose name or full name (see `get_full_name`) matches the
+    /// regular expression `pattern`, in the order the fields appear in the document
+    pub fn find_fields(&self, pattern: &str) -> Result<Vec<String>, regex::Error> {
+        let regex = regex::Regex::new(pattern)?;
+        Ok(self.field_order.iter()
+            .filter(|name| regex.is_match(name) || matches!(self.get_full_name(name), Some(full_name) if regex.is_match(&full_name)))
+            .cloned()
+            .collect())
+    }
+
+    /// Gets the full name of a field, which joins the names of the field and all of its parents
+    /// with the separator from `LoadOptions`
+    pub fn get_full_name(&self, name: &str) -> Option<String> {
+        Some(self.get_name_parts(name)?.join(&self.name_separator.to_string()))
+    }
+
+    /// Gets the names of a field and all of its parents, outermost first
+    fn get_name_parts(&self, name: &str) -> Option<Vec<String>> {
+        let mut parts = Vec::new();
+        let mut current = self.form_fields.get(name).cloned();
+        while let Some(field_id) = current {
+            let field = self.doc.objects.get(&field_id)?.as_dict().ok()?;
+            if let Ok(&Object::String(ref string_u8, _)) = field.get(b"T") {
+                parts.push(Form::get_form_name(string_u8.clone()).ok()?);
+            }
+            current = field.get(b"Parent").and_then(Object::as_reference).ok();
+        }
+        parts.reverse();
+        Some(parts)
+    }
+
+    /// Maps a name given by the user to the name of a field. Field names carry an index suffix
+    /// like `[0]`, which may be left out. Full names (see `get_full_name`) are accepted as well.
+    fn resolve_name(&self, name: &str) -> String {
+        if self.form_fields.contains_key(name) {
+            return name.to_owned();
+        }
+        let mut new_name = name.to_owned();
+        new_name.push_str("[0]");
+        if !self.form_fields.contains_key(&new_name) && name.contains(self.name_separator) {
+            let full_name_match = self.form_fields.keys().find(|key| match self.get_full_name(key) {
+                Some(full_name) => full_name == name || full_name == new_name,
+                None => false
+            });
+            if let Some(key) = full_name_match {
+                return key.clone();
+            }
+        }
+        new_name
+    }
+
+    /// Finds the check boxes that share a parent field with the check box `name`, including itself,
+    /// in discovery order. Such groups often rely on JavaScript to keep only one box checked.
+    /// Returns None if the field is not a check box or has no sibling check boxes.
+    pub fn checkbox_group(&self, name: &str) -> Option<Vec<String>> {
+        let name = self.resolve_name(name);
+        if !matches!(self.get_type(&name), Ok(FieldType::CheckBox)) {
+            return None;
+        }
+        let parent_of = |field_name: &str| {
+            let field_id = self.form_fields.get(field_name)?;
+            self.doc.objects.get(field_id)?.as_dict().ok()?.get(b"Parent").and_then(Object::as_reference).ok()
+        };
+        let parent = parent_of(&name)?;
+        let group: Vec<String> = self.field_order.iter()
+            .filter(|sibling| parent_of(sibling) == Some(parent) && matches!(self.get_type(sibling), Ok(FieldType::CheckBox)))
+            .cloned()
+            .collect();
+        if group.len() > 1 { Some(group) } else { None }
+    }
+
+    /// Returns true if the field is a terminal field, which takes input itself instead of only
+    /// grouping other fields: it has a type and its kids, if any, are all widgets
+    pub fn is_terminal(&self, name: &str) -> bool {
+        let field_id = match self.form_fields.get(name) {
+            Some(field_id) => *field_id,
+            None => return false
+        };
+        let has_type = matches!(self.doc.objects.get(&field_id).map(Object::as_dict), Some(Ok(field)) if field.has(b"FT"));
+        has_type && self.get_descendant_fields(field_id).is_empty()
+    }
+
+    /// Returns true if both names resolve to the same field, e.g. a full name and the field's own
+    /// name. Names that don't resolve to any field never refer to the same field.
+    pub fn same_field(&self, a: &str, b: &str) -> bool {
+        match (self.form_fields.get(&self.resolve_name(a)), self.form_fields.get(&self.resolve_name(b))) {
+            (Some(a), Some(b)) => a == b,
+            _ => false
+        }
+    }
+
+    /// Applies a state as returned by `get_state` to a field. Only the selection of a state is
+    /// applied; its options are ignored.
+    /// If the variant of `state` does not match the type of the field, or the field is a push
+    /// button, which has no state to set, returns ValueError
+    pub fn set_state(&mut self, name: &str, state: FieldState) -> Result<(), ValueError> {
+        let field_type = self.get_type(name).map_err(|_| ValueError::TypeMismatch)?;
+        match (field_type, state) {
+            (FieldType::Text, FieldState::Text { text }) => self.set_text(name, text),
+            (FieldType::CheckBox, FieldState::CheckBox { is_checked }) => self.set_check_box(name, is_checked),
+            (FieldType::Radio, FieldState::Radio { selected, .. }) => self.set_radio(name, selected),
+            (FieldType::ListBox, FieldState::ListBox { selected, .. })
+            | (FieldType::ComboBox, FieldState::ComboBox { selected, .. }) => self.set_choice(name, selected),
+            _ => Err(ValueError::TypeMismatch)
+        }
+    }
+
+    /// Copies the values of all fields that `other` has as well, like when filling a form in
+    /// stages. Fields are matched by name and must have the same type. Push buttons and radio
+    /// fields without any selection are skipped.
+    pub fn copy_values_from(&mut self, other: &Form) -> Result<(), FieldError> {
+        for name in self.field_order.clone() {
+            if !other.form_fields.contains_key(&name) {
+                continue;
+            }
+            let state = match other.get_state(&name) {
+                FieldState::Button { .. } => continue,
+                FieldState::Radio { ref selected, .. } if selected.is_empty() || selected == "Off" => continue,
+                state => state
+            };
+            self.set_state(&name, state).map_err(|e| {
+                FieldError::new(e, name.clone(), other.get_value_string(&name).unwrap_or_default())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the form has exactly the expected fields with the expected types. Returns one
+    /// message per missing field, field of another type and unexpected extra field.
+    pub fn check_schema(&self, expected: &[(String, FieldType)]) -> Result<(), Vec<String>> {
+        let mut mismatches = Vec::new();
+        for (name, expected_type) in expected {
+            match self.get_type(name) {
+                Ok(field_type) if field_type == *expected_type => {}
+                Ok(field_type) => mismatches.push(format!("field {} is {:?}, expected {:?}", name, field_type, expected_type)),
+                Err(_) => mismatches.push(format!("missing field {}", name)),
+            }
+        }
+        for name in &self.field_order {
+            if !expected.iter().any(|(expected_name, _)| expected_name == name) {
+                mismatches.push(format!("unexpected field {}", name));
+            }
+        }
+        if mismatches.is_empty() { Ok(()) } else { Err(mismatches) }
+    }
+
+    /// Fills the formula
+    pub fn fill(&mut self, fields: HashMap<String, String>) -> Result<(), FieldError> {
+        for (k, value) in fields {
+            let key = self.resolve_name(&k);
+
+            let map_v = value.clone();
+            let map_err = |x: ValueError| FieldError::new(x, key.clone(), map_v);
+
+            match self.get_type(&key) {
+                Ok(FieldType::Radio) => {
+                    self.set_radio(&key, value).map_err(map_err)?;
+                }
+                Ok(FieldType::CheckBox) => {
+                    self.set_check_box(&key, value.to_lowercase().eq("true")).map_err(map_err)?;
+                }
+                Ok(FieldType::Text) => {
+                    self.set_text(&key, value).map_err(map_err)?;
+                }
+                _ => {}
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Fills the formula like `fill`, but only if every value is valid. All values are checked
+    /// before any field is changed, in the order of the field names. If one is invalid, its error
+    /// is returned and the form is left untouched. Unlike `fill`, names that don't resolve to a
+    /// fillable field are an error as well, and check boxes only take `true` or `false`.
+    pub fn set_many_checked(&mut self, values: &HashMap<String, String>) -> Result<(), FieldError> {
+        let mut names: Vec<&String> = values.keys().collect();
+        names.sort();
+
+        let mut checked = Vec::new();
+        for name in names {
+            let value = &values[name];
+            let key = self.resolve_name(name);
+            let error = |x: ValueError| FieldError::new(x, key.clone(), value.clone());
+            match self.get_type(&key) {
+                Ok(FieldType::Radio) => {
+                    let field_id = self.form_fields[&key];
+                    if !self.get_radio_states(field_id).iter().any(|(state, option)| option == value || state == value) {
+                        return Err(error(ValueError::InvalidSelection));
+                    }
+                }
+                Ok(FieldType::ListBox) | Ok(FieldType::ComboBox) => {
+                    if !self.get_options(&key, false).map_err(|_| error(ValueError::TypeMismatch))?.contains(value) {
+                        return Err(error(ValueError::InvalidSelection));
+                    }
+                }
+                Ok(FieldType::CheckBox) => {
+                    if !value.eq_ignore_ascii_case("true") && !value.eq_ignore_ascii_case("false") {
+                        return Err(error(ValueError::InvalidSelection));
+                    }
+                }
+                Ok(FieldType::Text) => {}
+                _ => return Err(error(ValueError::TypeMismatch))
+            }
+            checked.push((key, value.clone()));
+        }
+
+        for (key, value) in checked {
+            let map_err = |x: ValueError| FieldError::new(x, key.clone(), value.clone());
+            match self.get_type(&key) {
+                Ok(FieldType::Radio) => self.set_radio(&key, value.clone()),
+                Ok(FieldType::CheckBox) => self.set_check_box(&key, value.to_lowercase().eq("true")),
+                Ok(FieldType::Text) => self.set_text(&key, value.clone()),
+                _ => self.set_choice(&key, vec![value.clone()]),
+            }.map_err(map_err)?;
+        }
+        Ok(())
+    }
+
+    /// Fills the formula from a JSON object. Booleans set check boxes, arrays select the options of
+    /// list and combo boxes, and strings fill text fields, radios and single choices.
+    pub fn fill_json(&mut self, value: &serde_json::Value) -> Result<(), FieldError> {
+        let fields = value.as_object()
+            .ok_or_else(|| FieldError::new(ValueError::TypeMismatch, String::new(), value.to_string()))?;
+
+        for (k, value) in fields {
+            let key = self.resolve_name(k);
+            let map_err = |x: ValueError| FieldError::new(x, key.clone(), value.to_string());
+
+            let field_type = match self.get_type(&key) {
+                Ok(field_type) => field_type,
+                Err(_) => continue
+            };
+            match (field_type, value) {
+                (_, serde_json::Value::Null) => {}
+                (FieldType::CheckBox, &serde_json::Value::Bool(is_checked)) => {
+                    self.set_check_box(&key, is_checked).map_err(map_err)?;
+                }
+                (FieldType::CheckBox, &serde_json::Value::String(ref s)) => {
+                    self.set_check_box(&key, s.to_lowercase().eq("true")).map_err(map_err)?;
+                }
+                (FieldType::Radio, &serde_json::Value::String(ref s)) => {
+                    self.set_radio(&key, s.clone()).map_err(map_err)?;
+                }
+                (FieldType::ListBox, &serde_json::Value::String(ref s)) | (FieldType::ComboBox, &serde_json::Value::String(ref s)) => {
+                    self.set_choice(&key, vec![s.clone()]).map_err(map_err)?;
+                }
+                (FieldType::ListBox, &serde_json::Value::Array(ref choices)) | (FieldType::ComboBox, &serde_json::Value::Array(ref choices)) => {
+                    let choices = choices.iter()
+                        .map(|choice| choice.as_str().map(str::to_owned).ok_or(ValueError::InvalidSelection))
+                        .collect::<Result<Vec<String>, ValueError>>()
+                        .map_err(map_err)?;
+                    self.set_choice(&key, choices).map_err(map_err)?;
+                }
+                (FieldType::Text, &serde_json::Value::String(ref s)) => {
+                    self.set_text(&key, s.clone()).map_err(map_err)?;
+                }
+                (FieldType::Text, &serde_json::Value::Number(ref n)) => {
+                    self.set_text(&key, n.to_string()).map_err(map_err)?;
+                }
+                (FieldType::Button, _) => {}
+                _ => return Err(map_err(ValueError::TypeMismatch))
+            };
+        }
+
+        Ok(())
+    }
+
+
+    /// Resolves the names for a subset export. Unknown names are skipped if `skip_unknown` is set
+    /// and fail the export otherwise.
+    fn resolve_subset(&self, names: &[String], skip_unknown: bool) -> Result<Vec<String>, LoadError> {
+        let mut res = Vec::new();
+        for name in names {
+            let key = self.resolve_name(name);
+            if self.form_fields.contains_key(&key) {
+                res.push(key);
+            } else if !skip_unknown {
+                return Err(LoadError::DictionaryKeyNotFound);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Exports the values of the named fields as a JSON object in the format `fill_json` accepts.
+    /// Push buttons have no value and are left out. Names that don't resolve to a field are
+    /// skipped if `skip_unknown` is set and return an error otherwise.
+    pub fn export_json_subset(&self, names: &[String], skip_unknown: bool) -> Result<serde_json::Value, LoadError> {
+        let mut res = serde_json::Map::new();
+        for key in self.resolve_subset(names, skip_unknown)? {
+            let value = match self.get_state(&key) {
+                FieldState::Button { .. } => continue,
+                FieldState::Text { text } => serde_json::Value::String(text),
+                FieldState::CheckBox { is_checked } => serde_json::Value::Bool(is_checked),
+                FieldState::Radio { selected, .. } => if selected.is_empty() || selected == "Off" {
+                    serde_json::Value::Null
+                } else {
+                    serde_json::Value::String(selected)
+                },
+                FieldState::ListBox { selected, .. } | FieldState::ComboBox { selected, .. } =>
+                    serde_json::Value::Array(selected.into_iter().map(serde_json::Value::String).collect()),
+            };
+            res.insert(key, value);
+        }
+        Ok(serde_json::Value::Object(res))
+    }
+
+    /// Exports the values of all fields as CSV with a `name,value` header, in the order the fields
+    /// appear in the document. Check boxes read `true` or `false`, multiple selected options are
+    /// joined with `; ` and push buttons are left out.
+    pub fn export_csv(&self) -> String {
+        let quote = |s: &str| if s.contains(&[',', '"', '\n', '\r'][..]) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_owned()
+        };
+
+        let mut res = String::from("name,value\r\n");
+        for name in &self.field_order {
+            let value = match self.get_state(name) {
+                FieldState::Button { .. } => continue,
+                FieldState::Text { text } => text,
+                FieldState::CheckBox { is_checked } => is_checked.to_string(),
+                FieldState::Radio { selected, .. } => if selected == "Off" { String::new() } else { selected },
+                FieldState::ListBox { selected, .. } | FieldState::ComboBox { selected, .. } => selected.join("; "),
+            };
+            res.push_str(&quote(name));
+            res.push(',');
+            res.push_str(&quote(&value));
+            res.push_str("\r\n");
+        }
+        res
+    }
+
+    /// Exports all fields as plain XML, one `field` element per field in document order. Each
+    /// element carries the field's name, type (see `field_type_str`) and whether it is required,
+    /// and holds an `option` element per option and a `value` element per value.
+    pub fn to_xml(&self) -> Result<String, LoadError> {
+        let mut res = String::from("<form>\n");
+        for name in &self.field_order {
+            let field_id = self.form_fields[name];
+            let flags = FieldFlags::from_bits_truncate(self.get_inherited(field_id, b"Ff").and_then(|ff| ff.as_i64().ok()).unwrap_or(0) as u32);
+            res.push_str(&format!("<field name=\"{}\" type=\"{}\" required=\"{}\">",
+                escape_xml(name), self.field_type_str(name)?, flags.contains(FieldFlags::REQUIRED)));
+            let (options, values) = match self.get_state(name) {
+                FieldState::Button { .. } => (Vec::new(), Vec::new()),
+                FieldState::Text { text } => (Vec::new(), vec![text]),
+                FieldState::CheckBox { is_checked } => (Vec::new(), vec![is_checked.to_string()]),
+                FieldState::Radio { selected, options } => {
+                    let options = options.into_iter().filter(|option| option != "Off").collect();
+                    (options, if selected == "Off" { Vec::new() } else { vec![selected] })
+                }
+                FieldState::ListBox { selected, options, .. } | FieldState::ComboBox { selected, options, .. } => (options, selected),
+            };
+            for option in options {
+                res.push_str(&format!("<option>{}</option>", escape_xml(&option)));
+            }
+            for value in values {
+                res.push_str(&format!("<value>{}</value>", escape_xml(&value)));
+            }
+            res.push_str("</field>\n");
+        }
+        res.push_str("</form>\n");
+        Ok(res)
+    }
+
+    /// Exports the values of the named fields as an FDF file. Fields are named by their full
+    /// name, as FDF requires. Names that don't resolve to a field are skipped if `skip_unknown` is
+    /// set and return an error otherwise.
+    pub fn export_fdf_subset(&self, names: &[String], skip_unknown: bool) -> Result<Vec<u8>, LoadError> {
+        let mut out = b"%FDF-1.2\n1 0 obj\n<< /FDF << /Fields [\n".to_vec();
+        for key in self.resolve_subset(names, skip_unknown)? {
+            let field_id = self.form_fields[&key];
+            let field = self.doc.objects.get(&field_id).ok_or(LoadError::NoSuchReference(field_id))?.as_dict()?;
+            let full_name = self.get_name_parts(&key).ok_or(LoadError::UnexpectedType)?.join(".");
+            out.extend_from_slice(b"<< /T ");
+            write_fdf_value(&Object::String(encode_pdf_text_string(&full_name), StringFormat::Hexadecimal), &mut out);
+            if let Ok(value) = field.get(b"V") {
+                let mut value_out = Vec::new();
+                let value = match value {
+                    &Object::Reference(oid) => self.doc.objects.get(&oid).ok_or(LoadError::NoSuchReference(oid))?,
+                    value => value
+                };
+                if write_fdf_value(value, &mut value_out) {
+                    out.extend_from_slice(b" /V ");
+                    out.extend_from_slice(&value_out);
+                }
+            }
+            out.extend_from_slice(b" >>\n");
+        }
+        out.extend_from_slice(b"] >> >>\nendobj\ntrailer\n<< /Root 1 0 R >>\n%%EOF\n");
+        Ok(out)
+    }
+
+    /// Saves the form to the specified path
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<(), io::Error> {
+        self.doc.save(path).map(|_| ())
     }
 
     /// Saves the form to the specified path
     pub fn save_to<W: io::Write>(&mut self, target: &mut W) -> Result<(), io::Error> {
         self.doc.save_to(target)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Saves the form with a classic cross-reference table, for consumers that predate the
+    /// cross-reference streams of PDF 1.5. lopdf always writes a classic table, so this only
+    /// leaves the entries that describe a cross-reference stream out of the written trailer. The
+    /// trailer of the form itself is not changed.
+    pub fn save_to_classic_xref<W: io::Write>(&mut self, target: &mut W) -> Result<(), io::Error> {
+        let trailer = self.doc.trailer.clone();
+        for key in &["Type", "W", "Index", "Filter", "DecodeParms", "Length", "XRefStm", "Prev"] {
+            self.doc.trailer.remove(key.as_bytes());
+        }
+        let res = self.doc.save_to(target);
+        self.doc.trailer = trailer;
+        res
+    }
+
+    /// Saves the form to the specified writer and returns the number of bytes written
+    pub fn save_to_counted<W: io::Write>(&mut self, target: &mut W) -> Result<usize, io::Error> {
+        let mut counter = CountingWriter { inner: target, count: 0 };
+        self.doc.save_to(&mut counter)?;
+        Ok(counter.count)
+    }
+}
+
+/// Iterates over the names of the fields in the order they appear in the document
+impl<'a> IntoIterator for &'a Form {
+    type Item = &'a str;
+    type IntoIter = std::iter::Map<std::slice::Iter<'a, String>, fn(&'a String) -> &'a str>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.field_order.iter().map(String::as_str)
+    }
+}
+
+/// Passes all writes on to `inner` while counting the bytes written
+struct CountingWriter<'a, W: io::Write> {
+    inner: &'a mut W,
+    count: usize,
+}
+
+impl<'a, W: io::Write> io::Write for CountingWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    /// Encodes `s` as a UTF-16BE text string with byte order mark, as form authoring tools do
+    fn text_string(s: &str) -> Object {
+        let mut bytes = vec![0xFE, 0xFF];
+        for unit in s.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_be_bytes());
+        }
+        Object::String(bytes, StringFormat::Literal)
+    }
+
+    /// Builds a one page document whose AcroForm lists the fields returned by `build` and loads it
+    fn build_form<F: FnOnce(&mut Document, ObjectId) -> Vec<ObjectId>>(build: F) -> Form {
+        Form::load_doc(build_doc(build)).unwrap()
+    }
+
+    /// A file in the temporary directory that no other run of the tests uses at the same time. The
+    /// file is removed when the test is done with it.
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            TempFile(std::env::temp_dir().join(format!("pdfformfill_{}_{}", std::process::id(), name)))
+        }
+    }
+
+    impl AsRef<Path> for TempFile {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// Gets the `Fields` array of the AcroForm of a document built by `build_doc`
+    fn acroform_fields(doc: &mut Document) -> &mut Vec<Object> {
+        let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        let acroform_id = doc.objects.get(&catalog_id).unwrap().as_dict().unwrap().get(b"AcroForm").unwrap().as_reference().unwrap();
+        doc.objects.get_mut(&acroform_id).unwrap().as_dict_mut().unwrap().get_mut(b"Fields").unwrap().as_array_mut().unwrap()
+    }
+
+    /// Builds a one page document whose AcroForm lists the fields returned by `build`
+    fn build_doc<F: FnOnce(&mut Document, ObjectId) -> Vec<ObjectId>>(build: F) -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }));
+
+        let fields = build(&mut doc, page_id);
+        let acroform_id = doc.add_object(dictionary! {
+            "Fields" => fields.into_iter().map(Object::Reference).collect::<Vec<Object>>(),
+        });
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "AcroForm" => acroform_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    /// Appends a new page to the document that contains `page_id`
+    fn add_page(doc: &mut Document, page_id: ObjectId) -> ObjectId {
+        let pages_id = doc.objects.get(&page_id).unwrap().as_dict().unwrap().get(b"Parent").unwrap().as_reference().unwrap();
+        let new_page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        let pages = doc.objects.get_mut(&pages_id).unwrap().as_dict_mut().unwrap();
+        pages.get_mut(b"Kids").unwrap().as_array_mut().unwrap().push(new_page_id.into());
+        let count = pages.get(b"Count").unwrap().as_i64().unwrap();
+        pages.set("Count", count + 1);
+        new_page_id
+    }
+
+    /// Adds a text field with the field flags `flags` to `doc`
+    fn text_field(doc: &mut Document, page_id: ObjectId, name: &str, flags: u32) -> ObjectId {
+        doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "P" => page_id,
+            "Rect" => vec![0.into(), 0.into(), 100.into(), 20.into()],
+            "FT" => "Tx",
+            "Ff" => flags as i64,
+            "T" => text_string(name),
+            "DA" => Object::string_literal("/Helv 12 Tf 0 g"),
+        })
+    }
+
+    /// Adds a choice field with the field flags `flags` and the given options to `doc`
+    fn choice_field(doc: &mut Document, page_id: ObjectId, name: &str, flags: u32, options: &[&str]) -> ObjectId {
+        doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "P" => page_id,
+            "Rect" => vec![0.into(), 0.into(), 100.into(), 20.into()],
+            "FT" => "Ch",
+            "Ff" => flags as i64,
+            "T" => text_string(name),
+            "DA" => Object::string_literal("/Helv 12 Tf 0 g"),
+            "Opt" => options.iter().map(|o| Object::string_literal(*o)).collect::<Vec<Object>>(),
+        })
+    }
+
+    /// Adds a check box to `doc` that turns on with the appearance state `on_state`
+    fn check_box(doc: &mut Document, page_id: ObjectId, name: &str, on_state: &str) -> ObjectId {
+        let on_id = doc.add_object(Stream::new(Dictionary::new(), vec![]));
+        let off_id = doc.add_object(Stream::new(Dictionary::new(), vec![]));
+        let mut normal = Dictionary::new();
+        normal.set(on_state, on_id);
+        normal.set("Off", off_id);
+        doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "P" => page_id,
+            "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+            "FT" => "Btn",
+            "T" => text_string(name),
+            "AP" => dictionary! { "N" => normal },
+            "AS" => "Off",
+            "V" => "Off",
+        })
+    }
+
+    /// Adds a push button to `doc` that triggers `action`
+    fn push_button(doc: &mut Document, page_id: ObjectId, name: &str, action: Dictionary) -> ObjectId {
+        doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "P" => page_id,
+            "Rect" => vec![0.into(), 0.into(), 50.into(), 20.into()],
+            "FT" => "Btn",
+            "Ff" => ButtonFlags::PUSHBUTTON.bits() as i64,
+            "T" => text_string(name),
+            "A" => action,
+        })
+    }
+
+    /// Adds a radio widget to `doc` that turns on with the appearance state `export`
+    fn radio_kid(doc: &mut Document, page_id: ObjectId, export: &str, label: &str) -> ObjectId {
+        let on_id = doc.add_object(Stream::new(Dictionary::new(), vec![]));
+        let off_id = doc.add_object(Stream::new(Dictionary::new(), vec![]));
+        let mut normal = Dictionary::new();
+        normal.set(export, on_id);
+        normal.set("Off", off_id);
+        doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "P" => page_id,
+            "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+            "TU" => text_string(label),
+            "AP" => dictionary! { "N" => normal },
+            "AS" => "Off",
+        })
+    }
+
+    /// Adds a radio field with one widget per `(export, label)` option to `doc`
+    fn radio_field(doc: &mut Document, page_id: ObjectId, name: &str, options: &[(&str, &str)]) -> ObjectId {
+        let kids: Vec<Object> = options.iter()
+            .map(|(export, label)| radio_kid(doc, page_id, export, label).into())
+            .collect();
+        doc.add_object(dictionary! {
+            "FT" => "Btn",
+            "Ff" => ButtonFlags::RADIO.bits() as i64,
+            "T" => text_string(name),
+            "Kids" => kids,
+        })
+    }
+
+    #[test]
+    pub fn test_get_names() -> Result<(), LoadError> {
+        let form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+
+        let names = form.get_field_names();
+
+        assert!(names.len() > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_set_radio_by_label() {
+        let mut form = build_form(|doc, page_id| {
+            vec![radio_field(doc, page_id, "Payment", &[("Choice1", "Cash"), ("Choice2", "Card")])]
+        });
+
+        let labels = form.radio_labels("Payment").unwrap();
+        assert_eq!(labels, vec![("Cash".to_owned(), "Choice1".to_owned()), ("Card".to_owned(), "Choice2".to_owned())]);
+
+        form.set_radio_by_label("Payment", "Card").unwrap();
+        match form.get_state("Payment") {
+            FieldState::Radio { selected, .. } => assert_eq!(selected, "Choice2"),
+            state => panic!("unexpected state {:?}", state),
+        }
+        assert!(form.set_radio_by_label("Payment", "Cheque").is_err());
+    }
+
+    #[test]
+    pub fn test_multiline_text_round_trip() {
+        let mut form = build_form(|doc, page_id| {
+            vec![text_field(doc, page_id, "Address", TextFlags::MULTILINE.bits())]
+        });
+
+        form.set_text("Address", "Line 1\nLine 2".to_owned()).unwrap();
+
+        let raw = form.get_field_by_name("Address".to_owned()).get(b"V").unwrap().as_str().unwrap().to_vec();
+        assert_eq!(raw, b"Line 1\rLine 2".to_vec());
+        assert_eq!(form.get_text("Address").unwrap(), "Line 1\nLine 2");
+    }
+
+    #[test]
+    pub fn test_is_empty() {
+        let form = build_form(|_, _| vec![]);
+        assert!(form.is_empty());
+
+        let form = build_form(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+        assert!(!form.is_empty());
+    }
+
+    #[test]
+    pub fn test_list_box_returns_all_options() {
+        let names: Vec<String> = (0..200).map(|i| format!("Option {}", i)).collect();
+        let options: Vec<&str> = names.iter().map(String::as_str).collect();
+        let form = build_form(|doc, page_id| {
+            let list_id = choice_field(doc, page_id, "Countries", 0, &options);
+            doc.objects.get_mut(&list_id).unwrap().as_dict_mut().unwrap().set("TI", 150);
+            vec![list_id]
+        });
+
+        match form.get_state("Countries") {
+            FieldState::ListBox { options, .. } => assert_eq!(options, names),
+            state => panic!("unexpected state {:?}", state),
+        }
+        assert_eq!(form.list_top_index("Countries").unwrap(), 150);
+    }
+
+    #[test]
+    pub fn test_field_name_for_object() {
+        let form = build_form(|doc, page_id| {
+            vec![radio_field(doc, page_id, "Payment", &[("Choice1", "Cash"), ("Choice2", "Card")])]
+        });
+        let field_id = form.get_field_id("Payment").unwrap();
+        let kid_id = form.get_field_by_name("Payment".to_owned()).get(b"Kids").unwrap().as_array().unwrap()[1]
+            .as_reference().unwrap();
+
+        assert_eq!(form.field_name_for_object(kid_id), Some("Payment".to_owned()));
+        assert_eq!(form.field_name_for_object(field_id), Some("Payment".to_owned()));
+        assert_eq!(form.field_name_for_object((9999, 0)), None);
+    }
+
+    #[test]
+    pub fn test_fill_json() {
+        let mut form = build_form(|doc, page_id| vec![
+            check_box(doc, page_id, "Agree", "Yes"),
+            choice_field(doc, page_id, "Colors", ChoiceFlags::MULTISELECT.bits(), &["Red", "Green", "Blue"]),
+        ]);
+
+        form.fill_json(&serde_json::json!({ "Agree": true, "Colors": ["Red", "Blue"] })).unwrap();
+
+        match form.get_state("Agree") {
+            FieldState::CheckBox { is_checked } => assert!(is_checked),
+            state => panic!("unexpected state {:?}", state),
+        }
+        match form.get_state("Colors") {
+            FieldState::ListBox { selected, .. } => assert_eq!(selected, vec!["Red".to_owned(), "Blue".to_owned()]),
+            state => panic!("unexpected state {:?}", state),
+        }
+    }
+
+    #[test]
+    pub fn test_drop_xfa() -> Result<(), LoadError> {
+        let mut form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+        assert!(form.has_xfa());
+
+        form.drop_xfa();
+        assert!(!form.has_xfa());
+
+        let mut buffer = Vec::new();
+        form.save_to(&mut buffer)?;
+        assert!(!Form::load_from(buffer.as_slice())?.has_xfa());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_widget_rects() {
+        let form = build_form(|doc, page_id| {
+            let second_page_id = add_page(doc, page_id);
+            let first = doc.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Widget",
+                "P" => page_id,
+                "Rect" => vec![10.into(), 20.into(), 110.into(), 40.into()],
+            });
+            let second = doc.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Widget",
+                "P" => second_page_id,
+                "Rect" => vec![Object::Real(5.5), 20.into(), 105.into(), 40.into()],
+            });
+            vec![doc.add_object(dictionary! {
+                "FT" => "Tx",
+                "T" => text_string("Name"),
+                "Kids" => vec![first.into(), second.into()],
+            })]
+        });
+
+        assert_eq!(form.widget_rects("Name"), vec![
+            (0, [10.0, 20.0, 110.0, 40.0]),
+            (1, [5.5, 20.0, 105.0, 40.0]),
+        ]);
+    }
+
+    #[test]
+    pub fn test_load_strict_rejects_unnamed_fields() -> Result<(), LoadError> {
+        let widget = |doc: &mut Document, page_id: ObjectId| doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "P" => page_id,
+            "Rect" => vec![0.into(), 0.into(), 100.into(), 20.into()],
+            "FT" => "Tx",
+        });
+        let mut form = build_form(|doc, page_id| {
+            // Widgets of a named field that repeat its FT are not unnamed fields
+            let kids: Vec<Object> = (0..2).map(|_| widget(doc, page_id).into()).collect();
+            let field_id = doc.add_object(dictionary! {
+                "FT" => "Tx",
+                "T" => text_string("Name"),
+                "Kids" => kids.clone(),
+            });
+            for kid in kids {
+                doc.objects.get_mut(&kid.as_reference().unwrap()).unwrap().as_dict_mut().unwrap().set("Parent", field_id);
+            }
+            vec![field_id, widget(doc, page_id)]
+        });
+        assert_eq!(form.len(), 1);
+
+        let path = TempFile::new("unnamed_field.pdf");
+        form.save(&path)?;
+        match Form::load_strict(&path) {
+            Err(LoadError::UnnamedFields(1)) => {}
+            result => panic!("unexpected result {:?}", result.map(|form| form.len())),
+        }
+        assert_eq!(Form::load(&path)?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_get_value_bytes() {
+        let mut form = build_form(|doc, page_id| vec![text_field(doc, page_id, "City", 0)]);
+        assert_eq!(form.get_value_bytes("City").unwrap(), None);
+
+        form.set_text("City", "Zürich".to_owned()).unwrap();
+
+        let bytes = form.get_value_bytes("City").unwrap().unwrap();
+        assert_eq!(&bytes[..2], &[0xFE, 0xFF]);
+        assert_eq!(&bytes[2..6], &[0x00, b'Z', 0x00, 0xFC]);
+        assert_eq!(form.get_text("City").unwrap(), "Zürich");
+    }
+
+    #[test]
+    pub fn test_set_check_box_value() {
+        let mut form = build_form(|doc, page_id| vec![check_box(doc, page_id, "Option", "Export1")]);
+
+        form.set_check_box_value("Option", Some("Export1")).unwrap();
+        let field = form.get_field_by_name("Option".to_owned());
+        assert_eq!(field.get(b"V").unwrap().as_name_str().unwrap(), "Export1");
+        assert_eq!(field.get(b"AS").unwrap().as_name_str().unwrap(), "Export1");
+
+        assert!(form.set_check_box_value("Option", Some("Yes")).is_err());
+
+        form.set_check_box_value("Option", None).unwrap();
+        let field = form.get_field_by_name("Option".to_owned());
+        assert_eq!(field.get(b"V").unwrap().as_name_str().unwrap(), "Off");
+        assert_eq!(field.get(b"AS").unwrap().as_name_str().unwrap(), "Off");
+    }
+
+    #[test]
+    pub fn test_save_to_counted() -> Result<(), LoadError> {
+        let mut form = build_form(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+
+        let mut buffer = Vec::new();
+        let count = form.save_to_counted(&mut buffer)?;
+
+        assert!(count > 0);
+        assert_eq!(count, buffer.len());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_get_options_sorted() {
+        let form = build_form(|doc, page_id| vec![
+            choice_field(doc, page_id, "Fruit", (ChoiceFlags::COBMO | ChoiceFlags::SORT).bits(), &["cherry", "Banana", "apple"]),
+        ]);
+
+        assert_eq!(form.get_options("Fruit", true).unwrap(), vec!["apple", "Banana", "cherry"]);
+        assert_eq!(form.get_options("Fruit", false).unwrap(), vec!["cherry", "Banana", "apple"]);
+    }
+
+    #[test]
+    pub fn test_get_choice_snapshot() {
+        let mut form = build_form(|doc, page_id| vec![
+            choice_field(doc, page_id, "Colors", ChoiceFlags::MULTISELECT.bits(), &["Red", "Green", "Blue"]),
+        ]);
+        form.set_choice("Colors", vec!["Green".to_owned(), "Blue".to_owned()]).unwrap();
+
+        let snapshot = form.get_choice_snapshot("Colors").unwrap();
+        assert_eq!(snapshot.options, vec!["Red", "Green", "Blue"]);
+        assert_eq!(snapshot.selected, vec!["Green", "Blue"]);
+        assert!(snapshot.multiselect);
+        assert!(!snapshot.editable);
+    }
+
+    #[test]
+    pub fn test_inline_field_dictionary() {
+        let mut doc = build_doc(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+        let inline = dictionary! {
+            "FT" => "Tx",
+            "T" => text_string("Inline"),
+            "Rect" => vec![0.into(), 0.into(), 100.into(), 20.into()],
+        };
+        acroform_fields(&mut doc).push(inline.into());
+
+        let mut form = Form::load_doc(doc).unwrap();
+
+        let mut names = form.get_field_names();
+        names.sort();
+        assert_eq!(names, vec!["Inline", "Name"]);
+        form.set_text("Inline", "Value".to_owned()).unwrap();
+        assert_eq!(form.get_text("Inline").unwrap(), "Value");
+    }
+
+    #[test]
+    pub fn test_repair_appearance_states() {
+        let mut form = build_form(|doc, page_id| {
+            let check_box_id = check_box(doc, page_id, "Agree", "Yes");
+            let radio_id = radio_field(doc, page_id, "Payment", &[("Choice1", "Cash"), ("Choice2", "Card")]);
+            let check_box = doc.objects.get_mut(&check_box_id).unwrap().as_dict_mut().unwrap();
+            check_box.set("V", "Yes");
+            check_box.remove(b"AS");
+            doc.objects.get_mut(&radio_id).unwrap().as_dict_mut().unwrap().set("V", "Choice2");
+            vec![check_box_id, radio_id]
+        });
+
+        form.repair_appearance_states();
+
+        let check_box = form.get_field_by_name("Agree".to_owned());
+        assert_eq!(check_box.get(b"AS").unwrap().as_name_str().unwrap(), "Yes");
+        let kids = form.get_field_by_name("Payment".to_owned()).get(b"Kids").unwrap().as_array().unwrap().clone();
+        let states: Vec<&str> = kids.iter()
+            .map(|kid| kid.deref(&form.doc).unwrap().as_dict().unwrap().get(b"AS").unwrap().as_name_str().unwrap())
+            .collect();
+        assert_eq!(states, vec!["Off", "Choice2"]);
+    }
+
+    #[test]
+    pub fn test_submit_button_action() {
+        let form = build_form(|doc, page_id| vec![
+            push_button(doc, page_id, "Submit", dictionary! {
+                "S" => "SubmitForm",
+                "F" => dictionary! {
+                    "FS" => "URL",
+                    "F" => Object::string_literal("https://example.com/submit"),
+                },
+            }),
+        ]);
+
+        match form.get_state("Submit") {
+            FieldState::Button { action } => assert_eq!(action, Some(ButtonAction::SubmitForm {
+                url: "https://example.com/submit".to_owned()
+            })),
+            state => panic!("unexpected state {:?}", state),
+        }
+    }
+
+    #[test]
+    pub fn test_submit_urls() {
+        let form = build_form(|doc, page_id| vec![
+            push_button(doc, page_id, "Send", dictionary! {
+                "S" => "SubmitForm",
+                "F" => Object::string_literal("http://forms.example.com/collect"),
+            }),
+            push_button(doc, page_id, "Clear", dictionary! { "S" => "ResetForm" }),
+            text_field(doc, page_id, "Name", 0),
+        ]);
+
+        assert_eq!(form.submit_urls(), vec![("Send".to_owned(), "http://forms.example.com/collect".to_owned())]);
+    }
+
+    #[test]
+    pub fn test_load_with_name_separator() -> Result<(), LoadError> {
+        let options = LoadOptions { name_separator: '/' };
+        let mut form = Form::load_with_options("./tests/assets/Formblatt_1.pdf", options)?;
+
+        assert_eq!(form.get_full_name("Datum_Eingabe[0]").unwrap(), "Formular1[0]/Formblatt1_Seite2[0]/Datum_Eingabe[0]");
+
+        let mut values = HashMap::new();
+        values.insert("Formular1[0]/Formblatt1_Seite2[0]/Datum_Eingabe".to_owned(), "01.01.2020".to_owned());
+        form.fill(values).unwrap();
+        assert_eq!(form.get_text("Datum_Eingabe[0]")?, "01.01.2020");
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_get_field_page_label() {
+        let mut doc = build_doc(|doc, page_id| {
+            let second_page_id = add_page(doc, page_id);
+            let third_page_id = add_page(doc, page_id);
+            vec![
+                text_field(doc, second_page_id, "Preface", 0),
+                text_field(doc, third_page_id, "Appendix", 0),
+            ]
+        });
+        let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        doc.objects.get_mut(&catalog_id).unwrap().as_dict_mut().unwrap().set("PageLabels", dictionary! {
+            "Nums" => vec![
+                0.into(), dictionary! { "S" => "r" }.into(),
+                2.into(), dictionary! { "S" => "D", "P" => Object::string_literal("A-") }.into(),
+            ],
+        });
+        let form = Form::load_doc(doc).unwrap();
+
+        assert_eq!(form.get_field_page_label("Preface").unwrap(), Some("ii".to_owned()));
+        assert_eq!(form.get_field_page_label("Appendix").unwrap(), Some("A-1".to_owned()));
+    }
+
+    #[test]
+    pub fn test_load_collecting_skips_broken_fields() -> Result<(), LoadError> {
+        let mut doc = build_doc(|doc, page_id| vec![
+            text_field(doc, page_id, "First", 0),
+            text_field(doc, page_id, "Second", 0),
+        ]);
+        acroform_fields(&mut doc).insert(1, Object::Reference((999, 0)));
+        let path = TempFile::new("broken_field.pdf");
+        doc.save(&path)?;
+
+        assert!(Form::load(&path).is_err());
+
+        let (form, errors) = Form::load_collecting(&path)?;
+        let mut names = form.get_field_names();
+        names.sort();
+        assert_eq!(names, vec!["First", "Second"]);
+        assert_eq!(errors.len(), 1);
+        match errors[0] {
+            ((999, 0), LoadError::NoSuchReference((999, 0))) => {}
+            ref error => panic!("unexpected error {:?}", error),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_set_choice_removes_combo_box_appearance() -> Result<(), LoadError> {
+        let mut form = build_form(|doc, page_id| {
+            let combo = choice_field(doc, page_id, "Combo", ChoiceFlags::COBMO.bits(), &["A", "B"]);
+            let appearance = doc.add_object(Stream::new(Dictionary::new(), vec![]));
+            doc.objects.get_mut(&combo).unwrap().as_dict_mut().unwrap().set("AP", dictionary! { "N" => appearance });
+            vec![combo]
+        });
+
+        form.set_choice("Combo", vec!["B".to_owned()]).unwrap();
+
+        let combo = form.get_field_by_name("Combo".to_owned());
+        assert!(!combo.has(b"AP"));
+        assert!(matches!(form.get_acroform()?.get(b"NeedAppearances"), Ok(&Object::Boolean(true))));
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_load_from_limited() -> Result<(), LoadError> {
+        let mut doc = build_doc(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes)?;
+
+        match Form::load_from_limited(&bytes[..], 16) {
+            Err(LoadError::TooLarge) => {}
+            other => panic!("expected TooLarge, got {:?}", other.map(|form| form.len()))
+        }
+        let form = Form::load_from_limited(&bytes[..], bytes.len())?;
+        assert_eq!(form.get_field_names(), vec!["Name"]);
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_tab_order() {
+        let mut ids = Vec::new();
+        let mut doc = build_doc(|doc, page_id| {
+            ids = vec![
+                text_field(doc, page_id, "First", 0),
+                text_field(doc, page_id, "Second", 0),
+                text_field(doc, page_id, "Third", 0),
+            ];
+            let page = doc.objects.get_mut(&page_id).unwrap().as_dict_mut().unwrap();
+            page.set("Annots", vec![ids[2].into(), ids[0].into(), ids[1].into()]);
+            ids.clone()
+        });
+        let form = Form::load_doc(doc.clone()).unwrap();
+        assert_eq!(form.tab_order(), vec!["Third", "First", "Second"]);
+
+        // With row order the position on the page decides
+        for (field_id, top) in ids.iter().zip(&[700, 600, 650]) {
+            doc.objects.get_mut(field_id).unwrap().as_dict_mut().unwrap()
+                .set("Rect", vec![0.into(), (top - 20).into(), 100.into(), (*top).into()]);
+        }
+        let page_id = doc.get_pages()[&1];
+        doc.objects.get_mut(&page_id).unwrap().as_dict_mut().unwrap().set("Tabs", "R");
+        let form = Form::load_doc(doc).unwrap();
+        assert_eq!(form.tab_order(), vec!["First", "Third", "Second"]);
+    }
+
+    #[test]
+    pub fn test_toggle_check_box() {
+        let mut form = build_form(|doc, page_id| vec![check_box(doc, page_id, "Box", "1")]);
+
+        assert!(form.toggle_check_box("Box").unwrap());
+        let field = form.get_field_by_name("Box".to_owned());
+        assert_eq!(field.get(b"V").unwrap().as_name_str().unwrap(), "1");
+        assert_eq!(field.get(b"AS").unwrap().as_name_str().unwrap(), "1");
+
+        assert!(!form.toggle_check_box("Box").unwrap());
+        let field = form.get_field_by_name("Box".to_owned());
+        assert_eq!(field.get(b"V").unwrap().as_name_str().unwrap(), "Off");
+        assert_eq!(field.get(b"AS").unwrap().as_name_str().unwrap(), "Off");
+    }
+
+    #[test]
+    pub fn test_radio_options_from_opt() {
+        let mut form = build_form(|doc, page_id| {
+            let kids: Vec<Object> = vec![radio_kid(doc, page_id, "0", "").into(), radio_kid(doc, page_id, "1", "").into()];
+            vec![doc.add_object(dictionary! {
+                "FT" => "Btn",
+                "Ff" => ButtonFlags::RADIO.bits() as i64,
+                "T" => text_string("Size"),
+                "Opt" => vec![Object::string_literal("Small"), text_string("Größer")],
+                "Kids" => kids,
+            })]
+        });
+        let name = "Size".to_owned();
+
+        match form.get_state(&name) {
+            FieldState::Radio { options, .. } => {
+                assert!(options.contains(&"Small".to_owned()));
+                assert!(options.contains(&"Größer".to_owned()));
+                assert!(!options.contains(&"1".to_owned()));
+            }
+            state => panic!("unexpected state {:?}", state),
+        }
+
+        form.set_radio(&name, "Größer".to_owned()).unwrap();
+        match form.get_state(&name) {
+            FieldState::Radio { selected, .. } => assert_eq!(selected, "Größer"),
+            state => panic!("unexpected state {:?}", state),
+        }
+        let field = form.get_field_by_name(name);
+        assert_eq!(field.get(b"V").unwrap().as_name_str().unwrap(), "1");
+    }
+
+    #[test]
+    pub fn test_text_field_values() {
+        let mut form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Zip", 0),
+            check_box(doc, page_id, "Agree", "Yes"),
+            text_field(doc, page_id, "City", 0),
+            text_field(doc, page_id, "Street", 0),
+        ]);
+        form.set_text("City", "Magdeburg".to_owned()).unwrap();
+        form.set_text("Zip", "39104".to_owned()).unwrap();
+
+        assert_eq!(form.text_field_values(), vec![
+            ("Zip".to_owned(), "39104".to_owned()),
+            ("City".to_owned(), "Magdeburg".to_owned()),
+            ("Street".to_owned(), "".to_owned()),
+        ]);
+    }
+
+    #[test]
+    pub fn test_set_text_returning() {
+        let mut form = build_form(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+
+        assert_eq!(form.set_text_returning("Name", "Erika".to_owned()).unwrap(), "");
+        assert_eq!(form.set_text_returning("Name", "Max").unwrap(), "Erika");
+        assert_eq!(form.get_text("Name").unwrap(), "Max");
+    }
+
+    #[test]
+    pub fn test_pdf_version() -> Result<(), LoadError> {
+        let form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+        assert_eq!(form.pdf_version(), "1.6");
+        assert!(!form.is_linearized());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_export_subset() {
+        let mut form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Name", 0),
+            text_field(doc, page_id, "Iban", 0),
+            check_box(doc, page_id, "Agree", "Yes"),
+            choice_field(doc, page_id, "Color", 0, &["Red", "Green"]),
+        ]);
+        form.set_text("Name", "Erika".to_owned()).unwrap();
+        form.set_text("Iban", "DE00 0000".to_owned()).unwrap();
+        form.set_check_box("Agree", true).unwrap();
+        let names = vec!["Name".to_owned(), "Agree".to_owned(), "Unknown".to_owned()];
+
+        assert!(form.export_json_subset(&names, false).is_err());
+        assert_eq!(form.export_json_subset(&names, true).unwrap(), serde_json::json!({ "Name": "Erika", "Agree": true }));
+
+        assert!(form.export_fdf_subset(&names, false).is_err());
+        let fdf = String::from_utf8(form.export_fdf_subset(&names, true).unwrap()).unwrap();
+        assert!(fdf.starts_with("%FDF-1.2"));
+        assert!(fdf.contains("<< /T <4E616D65> /V <4572696B61> >>"));
+        assert!(fdf.contains("<< /T <4167726565> /V /Yes >>"));
+        assert!(!fdf.contains("<4962616E>"));
+        assert!(!fdf.contains("<436F6C6F72>"));
+    }
+
+    #[test]
+    pub fn test_save_to_classic_xref() -> Result<(), LoadError> {
+        let mut form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+        assert!(form.uses_xref_stream());
+        let trailer_keys = |form: &Form| form.doc.trailer.iter().map(|(key, _)| key.clone()).collect::<Vec<Vec<u8>>>();
+        let keys = trailer_keys(&form);
+
+        let mut buffer = Vec::new();
+        form.save_to_classic_xref(&mut buffer)?;
+        let output = String::from_utf8_lossy(&buffer);
+        assert!(output.contains("\nxref\n"));
+        assert!(!output.contains("/XRef"));
+        assert!(!Form::load_from(buffer.as_slice())?.uses_xref_stream());
+        assert_eq!(trailer_keys(&form), keys);
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_text_value_stream() {
+        let form = build_form(|doc, page_id| {
+            let field_id = text_field(doc, page_id, "Notes", TextFlags::MULTILINE.bits());
+            let value_id = doc.add_object(Stream::new(Dictionary::new(), b"First line\rSecond line".to_vec()));
+            doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap().set("V", value_id);
+            vec![field_id]
+        });
+
+        assert_eq!(form.get_text("Notes").unwrap(), "First line\nSecond line");
+    }
+
+    #[test]
+    pub fn test_fields_matching() {
+        let form = build_form(|doc, page_id| vec![
+            check_box(doc, page_id, "agree_terms", "Yes"),
+            text_field(doc, page_id, "agree_comment", 0),
+            check_box(doc, page_id, "newsletter", "Yes"),
+            check_box(doc, page_id, "agree_privacy", "Yes"),
+        ]);
+
+        let names = form.fields_matching(|name, field_type| match field_type {
+            FieldType::CheckBox => name.starts_with("agree"),
+            _ => false
+        });
+        assert_eq!(names, vec!["agree_terms", "agree_privacy"]);
+    }
+
+    #[test]
+    pub fn test_export_csv() {
+        let mut form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Name", 0),
+            text_field(doc, page_id, "Address", TextFlags::MULTILINE.bits()),
+            check_box(doc, page_id, "Agree", "Yes"),
+            choice_field(doc, page_id, "Colors", ChoiceFlags::MULTISELECT.bits(), &["Red", "Green", "Blue"]),
+        ]);
+        form.set_text("Name", "Mustermann, Erika".to_owned()).unwrap();
+        form.set_text("Address", "Am \"Dom\" 1\nMagdeburg".to_owned()).unwrap();
+        form.set_check_box("Agree", true).unwrap();
+        form.set_choice("Colors", vec!["Red".to_owned(), "Blue".to_owned()]).unwrap();
+
+        assert_eq!(form.export_csv(), "name,value\r\n\
+            Name,\"Mustermann, Erika\"\r\n\
+            Address,\"Am \"\"Dom\"\" 1\nMagdeburg\"\r\n\
+            Agree,true\r\n\
+            Colors,Red; Blue\r\n");
+    }
+
+    #[test]
+    pub fn test_set_choice_sets_list_box_indices() {
+        let mut form = build_form(|doc, page_id| vec![choice_field(doc, page_id, "Color", 0, &["Red", "Green", "Blue"])]);
+        let name = "Color".to_owned();
+
+        form.set_choice(&name, vec!["Green".to_owned()]).unwrap();
+        let indices = form.get_field_by_name(name.clone()).get(b"I").unwrap().as_array().unwrap()
+            .iter().map(|index| index.as_i64().unwrap()).collect::<Vec<i64>>();
+        assert_eq!(indices, vec![1]);
+
+        form.set_choice(&name, vec![]).unwrap();
+        assert!(!form.get_field_by_name(name).has(b"I"));
+    }
+
+    #[test]
+    pub fn test_same_field() -> Result<(), LoadError> {
+        let form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+
+        assert!(form.same_field("Formular1[0].Formblatt1_Seite2[0].Datum_Eingabe[0]", "Datum_Eingabe"));
+        assert!(form.same_field("Datum_Eingabe[0]", "Datum_Eingabe"));
+        assert!(!form.same_field("Datum_Eingabe", "Einnahmen_Auswahl"));
+        assert!(!form.same_field("Unknown", "Unknown"));
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_embedded_file_names() {
+        let mut doc = build_doc(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+        let file_id = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile" }, b"Nachweis".to_vec()));
+        let spec_id = doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal("nachweis.txt"),
+            "EF" => dictionary! { "F" => file_id },
+        });
+        let kid_id = doc.add_object(dictionary! {
+            "Names" => vec![Object::string_literal("nachweis.txt"), spec_id.into()],
+        });
+        let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        doc.objects.get_mut(&catalog_id).unwrap().as_dict_mut().unwrap().set("Names", dictionary! {
+            "EmbeddedFiles" => dictionary! {
+                "Names" => vec![text_string("Anlage.pdf"), spec_id.into()],
+                "Kids" => vec![kid_id.into()],
+            },
+        });
+        let form = Form::load_doc(doc).unwrap();
+
+        assert_eq!(form.embedded_file_names(), vec!["Anlage.pdf", "nachweis.txt"]);
+    }
+
+    #[test]
+    pub fn test_setters_accept_str() {
+        let mut form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Name", 0),
+            check_box(doc, page_id, "Agree", "Yes"),
+            choice_field(doc, page_id, "Color", 0, &["Red", "Green"]),
+            radio_field(doc, page_id, "Payment", &[("Choice1", "Cash"), ("Choice2", "Card")]),
+        ]);
+
+        form.set_text("Name", "Erika").unwrap();
+        form.set_check_box("Agree", true).unwrap();
+        form.set_choice("Color", vec!["Green".to_owned()]).unwrap();
+        form.set_radio("Payment", "Choice2".to_owned()).unwrap();
+
+        assert_eq!(form.get_text("Name").unwrap(), "Erika");
+        assert_eq!(form.export_json_subset(&["Agree".to_owned(), "Color".to_owned(), "Payment".to_owned()], false).unwrap(),
+                   serde_json::json!({ "Agree": true, "Color": ["Green"], "Payment": "Choice2" }));
+    }
+
+    #[test]
+    pub fn test_options_len() {
+        let options: Vec<String> = (0..5000).map(|i| format!("Option {}", i)).collect();
+        let form = build_form(|doc, page_id| vec![
+            choice_field(doc, page_id, "Big", 0, &options.iter().map(String::as_str).collect::<Vec<&str>>()),
+            radio_field(doc, page_id, "Payment", &[("Choice1", "Cash"), ("Choice2", "Card")]),
+            text_field(doc, page_id, "Name", 0),
+        ]);
+
+        assert_eq!(form.options_len("Big").unwrap(), 5000);
+        match form.get_state("Payment") {
+            FieldState::Radio { options, .. } => assert_eq!(form.options_len("Payment").unwrap(), options.len()),
+            state => panic!("unexpected state {:?}", state),
+        }
+        assert!(form.options_len("Name").is_err());
+    }
+
+    #[test]
+    pub fn test_barcode_fields() {
+        let form = build_form(|doc, page_id| {
+            let barcode = text_field(doc, page_id, "Barcode", 0);
+            doc.objects.get_mut(&barcode).unwrap().as_dict_mut().unwrap().set("BarcodeType", "PDF417");
+            vec![text_field(doc, page_id, "Name", 0), barcode]
+        });
+
+        assert_eq!(form.barcode_fields(), vec!["Barcode"]);
+    }
+
+    #[test]
+    pub fn test_reindex() -> Result<(), LoadError> {
+        let mut form = build_form(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+        let page_id = form.doc.get_pages()[&1];
+
+        let field_id = text_field(&mut form.doc, page_id, "City", 0);
+        form.get_acroform_mut()?.get_mut(b"Fields")?.as_array_mut()?.push(field_id.into());
+        assert_eq!(form.get_field_id("City"), None);
+
+        form.reindex()?;
+        assert_eq!(form.get_field_id("City"), Some(field_id));
+        assert_eq!(form.text_field_values(), vec![("Name".to_owned(), "".to_owned()), ("City".to_owned(), "".to_owned())]);
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_inherited_default_value() {
+        let mut form = build_form(|doc, page_id| {
+            let parent_id = doc.new_object_id();
+            let child_id = text_field(doc, page_id, "Child", 0);
+            let own_id = text_field(doc, page_id, "Own", 0);
+            for (kid_id, default) in &[(child_id, None), (own_id, Some("Own default"))] {
+                let kid = doc.objects.get_mut(kid_id).unwrap().as_dict_mut().unwrap();
+                kid.set("Parent", parent_id);
+                if let Some(default) = default {
+                    kid.set("DV", Object::string_literal(*default));
+                }
+            }
+            doc.objects.insert(parent_id, Object::Dictionary(dictionary! {
+                "T" => text_string("Parent"),
+                "DV" => Object::string_literal("Inherited"),
+                "Kids" => vec![child_id.into(), own_id.into()],
+            }));
+            vec![parent_id]
+        });
+
+        assert_eq!(form.get_default("Child").unwrap(), vec!["Inherited"]);
+        assert_eq!(form.get_default("Own").unwrap(), vec!["Own default"]);
+
+        form.set_text("Child", "Changed").unwrap();
+        form.reset("Child").unwrap();
+        assert_eq!(form.get_text("Child").unwrap(), "Inherited");
+    }
+
+    #[test]
+    pub fn test_appearance_states() {
+        let form = build_form(|doc, page_id| vec![
+            check_box(doc, page_id, "Agree", "Yes"),
+            radio_field(doc, page_id, "Payment", &[("Choice1", "Cash"), ("Choice2", "Card")]),
+            text_field(doc, page_id, "Name", 0),
+        ]);
+
+        assert_eq!(form.appearance_states("Agree").unwrap(), vec!["Off", "Yes"]);
+        assert_eq!(form.appearance_states("Payment").unwrap(), vec!["Choice1", "Choice2", "Off"]);
+        assert!(form.appearance_states("Name").unwrap().is_empty());
+        assert!(form.appearance_states("Unknown").is_err());
+    }
+
+    #[test]
+    pub fn test_set_radio_single_widget() {
+        let mut form = build_form(|doc, page_id| {
+            let radio_id = radio_kid(doc, page_id, "Yes", "Only option");
+            let radio = doc.objects.get_mut(&radio_id).unwrap().as_dict_mut().unwrap();
+            radio.set("FT", "Btn");
+            radio.set("Ff", ButtonFlags::RADIO.bits() as i64);
+            radio.set("T", text_string("Single"));
+            vec![radio_id]
+        });
+
+        form.set_radio("Single", "Yes".to_owned()).unwrap();
+
+        let field = form.get_field_by_name("Single".to_owned());
+        assert_eq!(field.get(b"V").unwrap().as_name_str().unwrap(), "Yes");
+        assert_eq!(field.get(b"AS").unwrap().as_name_str().unwrap(), "Yes");
+        match form.get_state("Single") {
+            FieldState::Radio { selected, .. } => assert_eq!(selected, "Yes"),
+            state => panic!("unexpected state {:?}", state),
+        }
+    }
+
+    #[test]
+    pub fn test_clone_field() {
+        let mut form = build_form(|doc, page_id| vec![text_field(doc, page_id, "Name", TextFlags::MULTILINE.bits())]);
+        form.set_text("Name", "Erika").unwrap();
+
+        form.clone_field("Name", "Second name", 0, [0.0, 100.0, 100.0, 120.0]).unwrap();
+        form.set_text("Second name", "Max").unwrap();
+
+        assert_eq!(form.get_text("Name").unwrap(), "Erika");
+        assert_eq!(form.get_text("Second name").unwrap(), "Max");
+        assert_eq!(form.widget_rects("Second name"), vec![(0, [0.0, 100.0, 100.0, 120.0])]);
+        let clone = form.get_field_by_name("Second name".to_owned());
+        assert_eq!(clone.get(b"Ff").unwrap().as_i64().unwrap(), TextFlags::MULTILINE.bits() as i64);
+        assert!(form.clone_field("Name", "Second name", 0, [0.0; 4]).is_err());
+
+        // The clone is a field of its own in the saved document
+        let mut buffer = Vec::new();
+        form.save_to(&mut buffer).unwrap();
+        let form = Form::load_from(buffer.as_slice()).unwrap();
+        assert_eq!(form.get_text("Second name").unwrap(), "Max");
+    }
+
+    #[test]
+    pub fn test_text_color() {
+        let mut form = build_form(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+        assert_eq!(form.get_text_color("Name").unwrap(), Color::Gray(0.0));
+
+        let colors = vec![Color::Gray(0.5), Color::Rgb(1.0, 0.0, 0.25), Color::Cmyk(0.0, 1.0, 1.0, 0.0)];
+        for color in colors {
+            form.set_text_color("Name", color.clone()).unwrap();
+            assert_eq!(form.get_text_color("Name").unwrap(), color);
+        }
+        let da = form.get_field_by_name("Name".to_owned()).get(b"DA").unwrap().as_str().unwrap().to_vec();
+        assert_eq!(String::from_utf8(da).unwrap(), "/Helv 12 Tf 0 1 1 0 k");
+    }
+
+    #[test]
+    pub fn test_field_scripts() {
+        let form = build_form(|doc, page_id| {
+            let field_id = text_field(doc, page_id, "Amount", 0);
+            let format_id = doc.add_object(dictionary! {
+                "S" => "JavaScript",
+                "JS" => Object::string_literal("AFNumber_Format(2, 0, 0, 0, \"\", true);"),
+            });
+            doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap().set("AA", dictionary! {
+                "F" => format_id,
+                "U" => dictionary! { "S" => "ResetForm" },
+            });
+            vec![field_id, text_field(doc, page_id, "Name", 0)]
+        });
+
+        assert_eq!(form.field_scripts("Amount").unwrap(), vec![("F".to_owned(), "AFNumber_Format(2, 0, 0, 0, \"\", true);".to_owned())]);
+        assert!(form.field_scripts("Name").unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn test_strip_javascript() -> Result<(), LoadError> {
+        let mut form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+        let has_javascript = |form: &Form| {
+            let catalog = form.doc.trailer.get(b"Root").unwrap().deref(&form.doc).unwrap().as_dict().unwrap();
+            let names = match catalog.get(b"Names").unwrap() {
+                &Object::Reference(oid) => form.doc.objects.get(&oid).unwrap(),
+                names => names
+            };
+            names.as_dict().unwrap().has(b"JavaScript")
+        };
+        assert!(has_javascript(&form));
+        let scripted = "Datum_Eingabe[0]";
+        let field_id = form.get_field_id(scripted).unwrap();
+        form.doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap().set("AA", dictionary! {
+            "K" => dictionary! { "S" => "JavaScript", "JS" => Object::string_literal("AFDate_KeystrokeEx(\"dd.mm.yyyy\");") },
+        });
+        assert!(!form.field_scripts(scripted)?.is_empty());
+
+        form.strip_javascript();
+
+        assert!(!has_javascript(&form));
+        assert!(form.field_scripts(scripted)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_set_many_checked() {
+        let mut form = build_form(|doc, page_id| vec![
+            radio_field(doc, page_id, "Payment", &[("Cash", "Cash"), ("Card", "Card")]),
+            radio_field(doc, page_id, "Delivery", &[("Post", "Post"), ("Pickup", "Pickup")]),
+            text_field(doc, page_id, "Name", 0),
+            check_box(doc, page_id, "Agree", "Yes"),
+        ]);
+
+        let mut values = HashMap::new();
+        values.insert("Payment".to_owned(), "Card".to_owned());
+        values.insert("Delivery".to_owned(), "Drone".to_owned());
+        values.insert("Name".to_owned(), "Erika".to_owned());
+        values.insert("Agree".to_owned(), "ture".to_owned());
+        let error = form.set_many_checked(&values).unwrap_err();
+        assert_eq!(error.field(), "Agree");
+        assert!(matches!(error.error(), ValueError::InvalidSelection));
+
+        values.insert("Agree".to_owned(), "TRUE".to_owned());
+        let error = form.set_many_checked(&values).unwrap_err();
+        assert_eq!(error.field(), "Delivery");
+        match form.get_state("Payment") {
+            FieldState::Radio { selected, .. } => assert_eq!(selected, ""),
+            state => panic!("unexpected state {:?}", state),
+        }
+        assert_eq!(form.get_text("Name").unwrap(), "");
+
+        values.insert("Delivery".to_owned(), "Pickup".to_owned());
+        form.set_many_checked(&values).unwrap();
+        match form.get_state("Delivery") {
+            FieldState::Radio { selected, .. } => assert_eq!(selected, "Pickup"),
+            state => panic!("unexpected state {:?}", state),
+        }
+        assert_eq!(form.get_text("Name").unwrap(), "Erika");
+        assert!(matches!(form.get_state("Agree"), FieldState::CheckBox { is_checked: true }));
+    }
+
+    #[test]
+    pub fn test_field_type_str() {
+        let form = build_form(|doc, page_id| {
+            let signature_id = text_field(doc, page_id, "Signature", 0);
+            doc.objects.get_mut(&signature_id).unwrap().as_dict_mut().unwrap().set("FT", "Sig");
+            vec![
+                text_field(doc, page_id, "Name", 0),
+                check_box(doc, page_id, "Agree", "Yes"),
+                radio_field(doc, page_id, "Payment", &[("Cash", "Cash")]),
+                choice_field(doc, page_id, "List", 0, &["A"]),
+                choice_field(doc, page_id, "Combo", ChoiceFlags::COBMO.bits(), &["A"]),
+                push_button(doc, page_id, "Send", dictionary! { "S" => "ResetForm" }),
+                signature_id,
+            ]
+        });
+
+        let types: Vec<&str> = form.field_order.iter().map(|name| form.field_type_str(name).unwrap()).collect();
+        assert_eq!(types, vec!["text", "checkbox", "radio", "listbox", "combobox", "button", "signature"]);
+        assert!(form.field_type_str("Unknown").is_err());
+    }
+
+    #[test]
+    pub fn test_latin1_values() {
+        let form = build_form(|doc, page_id| {
+            let text_id = text_field(doc, page_id, "Name", 0);
+            doc.objects.get_mut(&text_id).unwrap().as_dict_mut().unwrap()
+                .set("V", Object::String(b"Ren\xE9".to_vec(), StringFormat::Literal));
+            let choice_id = choice_field(doc, page_id, "City", 0, &[]);
+            let choice = doc.objects.get_mut(&choice_id).unwrap().as_dict_mut().unwrap();
+            choice.set("Opt", vec![Object::String(b"Z\xFCrich".to_vec(), StringFormat::Literal)]);
+            choice.set("V", Object::String(b"Z\xFCrich".to_vec(), StringFormat::Literal));
+            vec![text_id, choice_id]
+        });
+
+        assert_eq!(form.get_text("Name").unwrap(), "René");
+        match form.get_state("City") {
+            FieldState::ListBox { selected, options, .. } => {
+                assert_eq!(selected, vec!["Zürich"]);
+                assert_eq!(options, vec!["Zürich"]);
+            }
+            state => panic!("unexpected state {:?}", state),
+        }
+    }
+
+    #[test]
+    pub fn test_load_large_form() {
+        let form = build_form(|doc, page_id| (0..5000).map(|i| text_field(doc, page_id, &format!("Field {}", i), 0)).collect());
+        assert_eq!(form.len(), 5000);
+        assert_eq!(form.field_order[4999], "Field 4999");
+    }
+
+    #[test]
+    pub fn test_other_annotations() {
+        let mut note_id = (0, 0);
+        let form = build_form(|doc, page_id| {
+            let field_id = text_field(doc, page_id, "Name", 0);
+            note_id = doc.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Text",
+                "Rect" => vec![0.into(), 0.into(), 20.into(), 20.into()],
+                "Contents" => Object::string_literal("Bitte leserlich ausfüllen"),
+            });
+            let page = doc.objects.get_mut(&page_id).unwrap().as_dict_mut().unwrap();
+            page.set("Annots", vec![field_id.into(), note_id.into()]);
+            vec![field_id]
+        });
+
+        assert_eq!(form.other_annotations(), vec![(0, note_id, "Text".to_owned())]);
+    }
+
+    #[test]
+    pub fn test_set_radio_in_unison() {
+        let mut form = build_form(|doc, page_id| vec![
+            radio_field(doc, page_id, "Payment", &[("Cash", "Cash"), ("Card", "Card"), ("Card", "Card again")]),
+            {
+                let kids: Vec<Object> = (0..3).map(|i| radio_kid(doc, page_id, &i.to_string(), "").into()).collect();
+                doc.add_object(dictionary! {
+                    "FT" => "Btn",
+                    "Ff" => (ButtonFlags::RADIO | ButtonFlags::RADIO_IN_UNISON).bits() as i64,
+                    "T" => text_string("Size"),
+                    "Opt" => vec![Object::string_literal("S"), Object::string_literal("L"), Object::string_literal("L")],
+                    "Kids" => kids,
+                })
+            },
+        ]);
+        let states = |form: &Form, name: &str| form.get_widget_ids(form.get_field_id(name).unwrap()).into_iter()
+            .map(|kid_id| form.doc.objects.get(&kid_id).unwrap().as_dict().unwrap().get(b"AS").unwrap().as_name_str().unwrap().to_owned())
+            .collect::<Vec<String>>();
+
+        form.set_radio("Payment", "Card".to_owned()).unwrap();
+        assert_eq!(states(&form, "Payment"), vec!["Off", "Card", "Card"]);
+
+        form.set_radio("Size", "L".to_owned()).unwrap();
+        assert_eq!(states(&form, "Size"), vec!["Off", "1", "2"]);
+    }
+
+    #[test]
+    pub fn test_field_debug_json() {
+        let form = build_form(|doc, page_id| vec![check_box(doc, page_id, "Agree", "Yes")]);
+
+        let json: serde_json::Value = serde_json::from_str(&form.field_debug_json("Agree").unwrap()).unwrap();
+        assert_eq!(json["FT"], "/Btn");
+        assert_eq!(json["T"], "Agree");
+        // The page is resolved, its parent is not
+        assert_eq!(json["P"]["Type"], "/Page");
+        assert!(json["P"]["Parent"].as_str().unwrap().ends_with(" 0 R"));
+        assert!(form.field_debug_json("Unknown").is_err());
+    }
 
     #[test]
-    pub fn test_get_names() -> Result<(), LoadError> {
+    pub fn test_set_choice_clears_stale_indices() {
+        let mut form = build_form(|doc, page_id| {
+            let list_id = choice_field(doc, page_id, "List", ChoiceFlags::MULTISELECT.bits(), &["A", "B", "C"]);
+            let combo_id = choice_field(doc, page_id, "Combo", ChoiceFlags::COBMO.bits(), &["A", "B"]);
+            for (field_id, index) in &[(list_id, 2), (combo_id, 1)] {
+                let field = doc.objects.get_mut(field_id).unwrap().as_dict_mut().unwrap();
+                field.set("V", Object::string_literal(if *index == 2 { "C" } else { "B" }));
+                field.set("I", vec![Object::Integer(*index)]);
+            }
+            vec![list_id, combo_id]
+        });
+
+        form.set_choice("List", vec![]).unwrap();
+        form.set_choice("Combo", vec!["A".to_owned()]).unwrap();
+
+        assert!(!form.get_field_by_name("List".to_owned()).has(b"I"));
+        assert!(!form.get_field_by_name("Combo".to_owned()).has(b"I"));
+        assert!(form.get_choice_snapshot("List").unwrap().selected.is_empty());
+
+        form.set_choice("Combo", vec![]).unwrap();
+        assert!(!form.get_field_by_name("Combo".to_owned()).has(b"I"));
+    }
+
+    #[test]
+    pub fn test_inferred_format() {
+        let form = build_form(|doc, page_id| {
+            let formats = [
+                ("Amount", "AFNumber_Format(2, 0, 0, 0, \"\", true);"),
+                ("Price", "AFNumber_Format(2, 2, 0, 0, \" €\", false);"),
+                ("Date", "AFDate_FormatEx(\"dd.mm.yyyy\");"),
+                ("Custom", "event.value = event.value.toUpperCase();"),
+            ];
+            let mut fields: Vec<ObjectId> = formats.iter().map(|(name, script)| {
+                let field_id = text_field(doc, page_id, name, 0);
+                doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap().set("AA", dictionary! {
+                    "F" => dictionary! { "S" => "JavaScript", "JS" => Object::string_literal(*script) },
+                });
+                field_id
+            }).collect();
+            fields.push(text_field(doc, page_id, "Plain", 0));
+            fields
+        });
+
+        assert_eq!(form.inferred_format("Amount"), Some(FieldFormat::Number));
+        assert_eq!(form.inferred_format("Price"), Some(FieldFormat::Currency));
+        assert_eq!(form.inferred_format("Date"), Some(FieldFormat::Date));
+        assert_eq!(form.inferred_format("Custom"), None);
+        assert_eq!(form.inferred_format("Plain"), None);
+    }
+
+    #[test]
+    pub fn test_checkbox_group() {
+        let form = build_form(|doc, page_id| {
+            let group_id = doc.new_object_id();
+            let kids: Vec<ObjectId> = ["Small", "Medium", "Large"].iter().map(|name| {
+                let kid_id = check_box(doc, page_id, name, "Yes");
+                doc.objects.get_mut(&kid_id).unwrap().as_dict_mut().unwrap().set("Parent", group_id);
+                kid_id
+            }).collect();
+            doc.objects.insert(group_id, Object::Dictionary(dictionary! {
+                "T" => text_string("Size"),
+                "Kids" => kids.into_iter().map(Object::Reference).collect::<Vec<Object>>(),
+            }));
+            vec![group_id, check_box(doc, page_id, "Agree", "Yes")]
+        });
+
+        let group = vec!["Small".to_owned(), "Medium".to_owned(), "Large".to_owned()];
+        assert_eq!(form.checkbox_group("Medium"), Some(group));
+        assert_eq!(form.checkbox_group("Agree"), None);
+    }
+
+    #[test]
+    pub fn test_set_text_keep_appearance() {
+        let mut form = build_form(|doc, page_id| {
+            let appearance_id = doc.add_object(Stream::new(Dictionary::new(), b"/Tx BMC EMC".to_vec()));
+            let field_id = text_field(doc, page_id, "Name", 0);
+            doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap().set("AP", dictionary! { "N" => appearance_id });
+            vec![field_id]
+        });
+
+        form.set_text_keep_appearance("Name", "Erika Mustermann").unwrap();
+        assert_eq!(form.get_text("Name").unwrap(), "Erika Mustermann");
+        assert!(form.get_field_by_name("Name".to_owned()).has(b"AP"));
+
+        form.set_text("Name", "Max Mustermann").unwrap();
+        assert!(!form.get_field_by_name("Name".to_owned()).has(b"AP"));
+    }
+
+    #[test]
+    pub fn test_get_widget_appearance() {
+        let form = build_form(|doc, page_id| {
+            let field_id = text_field(doc, page_id, "Framed", 0);
+            doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap().set("MK", dictionary! {
+                "BC" => vec![1.into(), 0.into(), 0.into()],
+                "BG" => vec![Object::Real(0.9)],
+                "R" => 90,
+            });
+            vec![field_id, text_field(doc, page_id, "Plain", 0)]
+        });
+
+        let appearance = form.get_widget_appearance("Framed").unwrap();
+        assert_eq!(appearance.border, Some(Color::Rgb(1.0, 0.0, 0.0)));
+        assert!(matches!(appearance.background, Some(Color::Gray(g)) if (g - 0.9).abs() < 1e-6));
+        assert_eq!(appearance.rotation, 90);
+        assert_eq!(form.get_widget_appearance("Plain").unwrap(), WidgetAppearance { background: None, border: None, rotation: 0 });
+    }
+
+    #[test]
+    pub fn test_find_fields() {
+        let form = build_form(|doc, page_id| {
+            let address_id = doc.new_object_id();
+            let kids: Vec<ObjectId> = ["street", "city"].iter().map(|name| {
+                let kid_id = text_field(doc, page_id, name, 0);
+                doc.objects.get_mut(&kid_id).unwrap().as_dict_mut().unwrap().set("Parent", address_id);
+                kid_id
+            }).collect();
+            doc.objects.insert(address_id, Object::Dictionary(dictionary! {
+                "T" => text_string("address"),
+                "Kids" => kids.into_iter().map(Object::Reference).collect::<Vec<Object>>(),
+            }));
+            vec![address_id, text_field(doc, page_id, "name", 0)]
+        });
+
+        assert_eq!(form.find_fields("^address\\..*").unwrap(), vec!["street".to_owned(), "city".to_owned()]);
+        assert_eq!(form.find_fields("^na").unwrap(), vec!["name".to_owned()]);
+        assert!(form.find_fields("(").is_err());
+    }
+
+    #[test]
+    pub fn test_set_radio_hierarchical() {
+        let form_doc = build_doc(|doc, page_id| {
+            let parent_id = doc.new_object_id();
+            let method_id = radio_field(doc, page_id, "Method", &[("Cash", "Cash"), ("Card", "Card")]);
+            let method = doc.objects.get_mut(&method_id).unwrap().as_dict_mut().unwrap();
+            method.set("Parent", parent_id);
+            method.set("V", "Cash");
+            doc.objects.insert(parent_id, Object::Dictionary(dictionary! {
+                "FT" => "Btn",
+                "Ff" => ButtonFlags::RADIO.bits() as i64,
+                "T" => text_string("Payment"),
+                "Kids" => vec![method_id.into()],
+            }));
+            vec![parent_id]
+        });
+        let mut form = Form::load_doc(form_doc).unwrap();
+
+        form.set_radio("Method", "Card".to_owned()).unwrap();
+        for name in &["Payment", "Method", "Payment.Method"] {
+            match form.get_state(&form.resolve_name(name)) {
+                FieldState::Radio { selected, options } => {
+                    assert_eq!(selected, "Card");
+                    assert_eq!(options, vec!["Cash".to_owned(), "Off".to_owned(), "Card".to_owned(), "Off".to_owned()]);
+                }
+                _ => panic!("{} is not a radio field", name)
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_get_appearance_stream() {
+        let content = b"/Tx BMC BT /Helv 12 Tf 2 5 Td (Hello) Tj ET EMC".to_vec();
+        let form = build_form(|doc, page_id| {
+            let appearance_id = doc.add_object(Stream::new(Dictionary::new(), content.clone()));
+            let field_id = text_field(doc, page_id, "Greeting", 0);
+            doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap().set("AP", dictionary! { "N" => appearance_id });
+            vec![field_id, text_field(doc, page_id, "Empty", 0), check_box(doc, page_id, "Agree", "Yes")]
+        });
+
+        assert_eq!(form.get_appearance_stream("Greeting", None).unwrap(), Some(content));
+        assert_eq!(form.get_appearance_stream("Empty", None).unwrap(), None);
+        assert_eq!(form.get_appearance_stream("Agree", Some("Yes")).unwrap(), Some(Vec::new()));
+        assert_eq!(form.get_appearance_stream("Agree", Some("Maybe")).unwrap(), None);
+    }
+
+    #[test]
+    pub fn test_pages_with_fields() {
+        let form = build_form(|doc, page_id| {
+            let second_page_id = add_page(doc, page_id);
+            add_page(doc, page_id);
+            let fourth_page_id = add_page(doc, page_id);
+            vec![
+                text_field(doc, page_id, "Name", 0),
+                text_field(doc, page_id, "Street", 0),
+                text_field(doc, fourth_page_id, "Signature", 0),
+                radio_field(doc, second_page_id, "Choice", &[("A", "A")]),
+            ]
+        });
+        assert_eq!(form.pages_with_fields(), 3);
+    }
+
+    #[test]
+    pub fn test_fill_check_box_on_state() {
+        let mut form = build_form(|doc, page_id| vec![check_box(doc, page_id, "Subscribe", "On")]);
+
+        let mut values = HashMap::new();
+        values.insert("Subscribe".to_owned(), "true".to_owned());
+        form.fill(values).unwrap();
+
+        match form.get_state("Subscribe") {
+            FieldState::CheckBox { is_checked } => assert!(is_checked),
+            _ => panic!("Subscribe is not a check box")
+        }
+        let field = form.get_field_by_name("Subscribe".to_owned());
+        assert_eq!(field.get(b"V").unwrap().as_name_str().unwrap(), "On");
+        assert_eq!(field.get(b"AS").unwrap().as_name_str().unwrap(), "On");
+    }
+
+    #[test]
+    pub fn test_reset_button_fields() {
+        let form = build_form(|doc, page_id| {
+            let name_id = text_field(doc, page_id, "Name", 0);
+            let street_id = text_field(doc, page_id, "Street", 0);
+            let clear_id = push_button(doc, page_id, "Clear", dictionary! {
+                "S" => "ResetForm",
+                "Fields" => vec![name_id.into(), text_string("Street")],
+            });
+            let clear_others_id = push_button(doc, page_id, "ClearOthers", Dictionary::new());
+            let clear_others = doc.objects.get_mut(&clear_others_id).unwrap().as_dict_mut().unwrap();
+            clear_others.remove(b"A");
+            clear_others.set("AA", dictionary! {
+                "U" => dictionary! { "S" => "ResetForm", "Fields" => vec![name_id.into()], "Flags" => 1 },
+            });
+            vec![name_id, street_id, clear_id, clear_others_id]
+        });
+
+        match form.get_state("Clear") {
+            FieldState::Button { action } => assert_eq!(action, Some(ButtonAction::ResetForm {
+                fields: vec!["Name".to_owned(), "Street".to_owned()],
+                exclude: false,
+            })),
+            _ => panic!("Clear is not a button")
+        }
+        match form.get_state("ClearOthers") {
+            FieldState::Button { action } => assert_eq!(action, Some(ButtonAction::ResetForm {
+                fields: vec!["Name".to_owned()],
+                exclude: true,
+            })),
+            _ => panic!("ClearOthers is not a button")
+        }
+    }
+
+    #[test]
+    pub fn test_iterate_field_names() {
+        let form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Name", 0),
+            check_box(doc, page_id, "Agree", "Yes"),
+            text_field(doc, page_id, "City", 0),
+        ]);
+
+        let mut names = Vec::new();
+        for name in &form {
+            names.push(name);
+        }
+        assert_eq!(names, vec!["Name", "Agree", "City"]);
+    }
+
+    #[test]
+    pub fn test_set_state() {
+        let mut form = build_form(|doc, page_id| vec![
+            check_box(doc, page_id, "Agree", "Yes"),
+            text_field(doc, page_id, "Name", 0),
+        ]);
+
+        let state = match form.get_state("Agree") {
+            FieldState::CheckBox { is_checked } => FieldState::CheckBox { is_checked: !is_checked },
+            _ => panic!("Agree is not a check box")
+        };
+        form.set_state("Agree", state).unwrap();
+        assert!(matches!(form.get_state("Agree"), FieldState::CheckBox { is_checked: true }));
+
+        assert!(form.set_state("Name", FieldState::CheckBox { is_checked: true }).is_err());
+        assert!(form.set_state("Unknown", FieldState::Text { text: "x".to_owned() }).is_err());
+    }
+
+    #[test]
+    pub fn test_indirect_kids() {
+        let mut form = build_form(|doc, page_id| {
+            let parent_id = doc.new_object_id();
+            let street_id = text_field(doc, page_id, "Street", 0);
+            doc.objects.get_mut(&street_id).unwrap().as_dict_mut().unwrap().set("Parent", parent_id);
+            let kids_id = doc.add_object(vec![Object::Reference(street_id)]);
+            doc.objects.insert(parent_id, Object::Dictionary(dictionary! {
+                "T" => text_string("Address"),
+                "Kids" => kids_id,
+            }));
+            vec![parent_id]
+        });
+
+        assert_eq!(form.field_order, vec!["Street".to_owned()]);
+        assert_eq!(form.get_full_name("Street").unwrap(), "Address.Street");
+        form.set_text("Street", "Breiter Weg 1").unwrap();
+        assert_eq!(form.get_text("Street").unwrap(), "Breiter Weg 1");
+    }
+
+    #[test]
+    pub fn test_to_xml() {
+        let mut form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Name", FieldFlags::REQUIRED.bits()),
+            check_box(doc, page_id, "Agree", "Yes"),
+            choice_field(doc, page_id, "Size", ChoiceFlags::COBMO.bits(), &["S", "M & L"]),
+        ]);
+        form.set_text("Name", "<Erika>").unwrap();
+        form.set_choice("Size", vec!["M & L".to_owned()]).unwrap();
+
+        assert_eq!(form.to_xml().unwrap(), "<form>\n\
+            <field name=\"Name\" type=\"text\" required=\"true\"><value>&lt;Erika&gt;</value></field>\n\
+            <field name=\"Agree\" type=\"checkbox\" required=\"false\"><value>false</value></field>\n\
+            <field name=\"Size\" type=\"combobox\" required=\"false\"><option>S</option><option>M &amp; L</option><value>M &amp; L</value></field>\n\
+            </form>\n");
+    }
+
+    #[test]
+    pub fn test_set_text_inherited_value() {
+        let mut form = build_form(|doc, page_id| {
+            // "Copy" inherits its value from a parent without a type of its own
+            let shared_id = doc.new_object_id();
+            let copy_id = text_field(doc, page_id, "Copy", 0);
+            doc.objects.get_mut(&copy_id).unwrap().as_dict_mut().unwrap().set("Parent", shared_id);
+            doc.objects.insert(shared_id, Object::Dictionary(dictionary! {
+                "T" => text_string("Shared"),
+                "V" => Object::string_literal("old"),
+                "Kids" => vec![copy_id.into()],
+            }));
+
+            // "Line" shares the value of the text field "Total" it is a kid of
+            let total_id = text_field(doc, page_id, "Total", 0);
+            let line_id = text_field(doc, page_id, "Line", 0);
+            doc.objects.get_mut(&line_id).unwrap().as_dict_mut().unwrap().set("Parent", total_id);
+            let total = doc.objects.get_mut(&total_id).unwrap().as_dict_mut().unwrap();
+            total.set("V", Object::string_literal("1"));
+            total.set("Kids", vec![Object::Reference(line_id)]);
+            vec![shared_id, total_id]
+        });
+
+        assert_eq!(form.get_text("Copy").unwrap(), "old");
+        form.set_text("Copy", "new").unwrap();
+        assert_eq!(form.get_text("Copy").unwrap(), "new");
+
+        assert_eq!(form.get_text("Line").unwrap(), "1");
+        form.set_text("Line", "2").unwrap();
+        assert_eq!(form.get_text("Line").unwrap(), "2");
+        assert_eq!(form.get_text("Total").unwrap(), "2");
+        assert!(!form.get_field_by_name("Line".to_owned()).has(b"V"));
+    }
+
+    #[test]
+    pub fn test_prune() {
+        let mut form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Name", 0),
+            check_box(doc, page_id, "Agree", "Yes"),
+        ]);
+        assert_eq!(form.prune(), 0);
+
+        // Drop the check box and its two appearance streams from the AcroForm
+        let agree_id = form.form_fields["Agree"];
+        form.get_acroform_mut().unwrap().get_mut(b"Fields").unwrap().as_array_mut().unwrap()
+            .retain(|field| field.as_reference().ok() != Some(agree_id));
+        let count = form.doc.objects.len();
+
+        assert_eq!(form.prune(), 3);
+        assert_eq!(form.doc.objects.len(), count - 3);
+        assert_eq!(form.field_order, vec!["Name".to_owned()]);
+        assert!(form.get_field_id("Agree").is_none());
+    }
+
+    #[test]
+    pub fn test_commit_on_change_combo_appearance() {
+        let mut form = build_form(|doc, page_id| vec![
+            choice_field(doc, page_id, "Country", (ChoiceFlags::COBMO | ChoiceFlags::COMMIT_ON_CHANGE).bits(), &["Deutschland", "Österreich"]),
+        ]);
+
+        form.set_choice("Country", vec!["Österreich".to_owned()]).unwrap();
+        let content = form.get_appearance_stream("Country", None).unwrap().unwrap();
+        let content = String::from_utf8(content).unwrap();
+        assert!(content.contains("/Helv 12 Tf 0 g"));
+        // "Österreich" in Latin-1
+        assert!(content.contains("<D6737465727265696368> Tj"));
+    }
+
+    #[test]
+    pub fn test_load_recovering() -> Result<(), LoadError> {
+        let mut form = build_form(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+        form.set_text("Name", "Erika Mustermann").unwrap();
+        let mut buffer = Vec::new();
+        form.save_to(&mut buffer)?;
+
+        // Point startxref into the middle of the file
+        let startxref = buffer.windows(9).rposition(|window| window == b"startxref").unwrap();
+        buffer.truncate(startxref);
+        buffer.extend_from_slice(b"startxref\n123\n%%EOF\n");
+        let path = TempFile::new("damaged_xref.pdf");
+        std::fs::write(&path, &buffer)?;
+
+        assert!(Form::load(&path).is_err());
+        let form = Form::load_recovering(&path)?;
+        assert_eq!(form.get_text("Name")?, "Erika Mustermann");
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_get_value_string() {
+        let mut form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Name", 0),
+            check_box(doc, page_id, "Subscribe", "Ja"),
+            check_box(doc, page_id, "Agree", "Yes"),
+            radio_field(doc, page_id, "Payment", &[("Cash", "Bar"), ("Card", "Karte")]),
+            choice_field(doc, page_id, "Sizes", ChoiceFlags::MULTISELECT.bits(), &["S", "M", "L"]),
+            push_button(doc, page_id, "Send", dictionary! { "S" => "ResetForm" }),
+        ]);
+        form.set_text("Name", "Erika").unwrap();
+        form.set_check_box("Subscribe", true).unwrap();
+        form.set_radio("Payment", "Card".to_owned()).unwrap();
+        form.set_choice("Sizes", vec!["S".to_owned(), "L".to_owned()]).unwrap();
+
+        assert_eq!(form.get_value_string("Name").unwrap(), "Erika");
+        assert_eq!(form.get_value_string("Subscribe").unwrap(), "Ja");
+        assert_eq!(form.get_value_string("Agree").unwrap(), "");
+        assert_eq!(form.get_value_string("Payment").unwrap(), "Card");
+        assert_eq!(form.get_value_string("Sizes").unwrap(), "S; L");
+        assert!(form.get_value_string("Send").is_err());
+        assert!(form.get_value_string("Unknown").is_err());
+    }
+
+    #[test]
+    pub fn test_fit_font_size() {
+        let short = fit_font_size("Ja", 100.0, 20.0);
+        let long = fit_font_size("Donaudampfschifffahrtsgesellschaftskapitän", 100.0, 20.0);
+        assert!(long < short);
+        assert!(short <= 12.0);
+
+        let content = String::from_utf8(single_line_appearance("/Helv 0 Tf 0 g", "Ja", 100.0, 20.0)).unwrap();
+        assert!(content.contains(&format!("/Helv {:.2} Tf 0 g", short)));
+    }
+
+    #[test]
+    pub fn test_is_terminal() {
+        let form = build_form(|doc, page_id| {
+            let total_id = text_field(doc, page_id, "Total", 0);
+            let line_ids: Vec<Object> = ["Line1", "Line2"].iter().map(|name| {
+                let line_id = text_field(doc, page_id, name, 0);
+                doc.objects.get_mut(&line_id).unwrap().as_dict_mut().unwrap().set("Parent", total_id);
+                Object::Reference(line_id)
+            }).collect();
+            doc.objects.get_mut(&total_id).unwrap().as_dict_mut().unwrap().set("Kids", line_ids);
+            vec![total_id, radio_field(doc, page_id, "Payment", &[("Cash", "Cash")])]
+        });
+
+        assert!(!form.is_terminal("Total"));
+        assert!(form.is_terminal("Line1"));
+        assert!(form.is_terminal("Line2"));
+        // Widget kids don't make a field a parent node
+        assert!(form.is_terminal("Payment"));
+        assert!(!form.is_terminal("Unknown"));
+    }
+
+    #[test]
+    pub fn test_set_check_box_on_name() {
+        let mut form = build_form(|doc, page_id| vec![check_box(doc, page_id, "Newsletter", "Abonniert")]);
+
+        assert!(matches!(form.set_check_box_on_name("Newsletter", "Yes"), Err(ValueError::InvalidSelection)));
+        form.set_check_box_on_name("Newsletter", "Abonniert").unwrap();
+        let field = form.get_field_by_name("Newsletter".to_owned());
+        assert_eq!(field.get(b"V").unwrap().as_name_str().unwrap(), "Abonniert");
+        assert_eq!(field.get(b"AS").unwrap().as_name_str().unwrap(), "Abonniert");
+    }
+
+    #[test]
+    pub fn test_get_field_names_sorted() {
+        let form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Street", 0),
+            text_field(doc, page_id, "City", 0),
+            check_box(doc, page_id, "Agree", "Yes"),
+            text_field(doc, page_id, "Name", 0),
+        ]);
+
+        assert_eq!(form.get_field_names_sorted(), vec!["Agree", "City", "Name", "Street"]);
+    }
+
+    #[test]
+    pub fn test_selection_from_indices() {
+        let form = build_form(|doc, page_id| {
+            let list_id = choice_field(doc, page_id, "Sizes", ChoiceFlags::MULTISELECT.bits(), &["S", "M", "L", "XL"]);
+            doc.objects.get_mut(&list_id).unwrap().as_dict_mut().unwrap().set("I", vec![1.into(), 3.into()]);
+            vec![list_id]
+        });
+
+        match form.get_state("Sizes") {
+            FieldState::ListBox { selected, .. } => assert_eq!(selected, vec!["M", "XL"]),
+            _ => panic!("Sizes is not a list box")
+        }
+    }
+
+    #[test]
+    pub fn test_count_selected() {
+        let mut form = build_form(|doc, page_id| vec![
+            choice_field(doc, page_id, "Sizes", ChoiceFlags::MULTISELECT.bits(), &["S", "M", "L"]),
+            radio_field(doc, page_id, "Payment", &[("Cash", "Cash"), ("Card", "Card")]),
+            text_field(doc, page_id, "Name", 0),
+        ]);
+        assert_eq!(form.count_selected("Sizes").unwrap(), 0);
+        assert_eq!(form.count_selected("Payment").unwrap(), 0);
+
+        form.set_choice("Sizes", vec!["S".to_owned(), "L".to_owned()]).unwrap();
+        form.set_radio("Payment", "Card".to_owned()).unwrap();
+        assert_eq!(form.count_selected("Sizes").unwrap(), 2);
+        assert_eq!(form.count_selected("Payment").unwrap(), 1);
+        assert!(form.count_selected("Name").is_err());
+        assert!(form.count_selected("Unknown").is_err());
+    }
+
+    #[test]
+    pub fn test_add_text_field_unicode_name() -> Result<(), LoadError> {
+        let mut form = build_form(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+        form.add_text_field("Straße", 0, [50.0, 700.0, 250.0, 720.0])?;
+        form.set_text("Straße", "Breiter Weg 1").unwrap();
+        assert!(matches!(form.add_text_field("Straße", 0, [0.0, 0.0, 10.0, 10.0]), Err(LoadError::DuplicateName(_))));
+
+        let mut buffer = Vec::new();
+        form.save_to(&mut buffer)?;
+        let form = Form::load_from(&buffer[..])?;
+        assert_eq!(form.get_field_names_sorted(), vec!["Name", "Straße"]);
+        assert_eq!(form.get_text("Straße")?, "Breiter Weg 1");
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_copy_values_from() -> Result<(), LoadError> {
+        let mut source = Form::load("./tests/assets/Formblatt_1.pdf")?;
+        source.set_text("Datum_Eingabe[0]", "01.01.2020").unwrap();
+        let radio_option = match source.get_state("Einnahmen_Auswahl[0]") {
+            FieldState::Radio { options, .. } => options.into_iter().find(|option| option != "Off").unwrap(),
+            _ => panic!("Einnahmen_Auswahl[0] is not a radio field")
+        };
+        source.set_radio("Einnahmen_Auswahl[0]", radio_option.clone()).unwrap();
+
+        let mut target = Form::load("./tests/assets/Formblatt_1.pdf")?;
+        target.copy_values_from(&source).unwrap();
+
+        assert_eq!(target.get_text("Datum_Eingabe[0]")?, "01.01.2020");
+        assert_eq!(target.get_value_string("Einnahmen_Auswahl[0]")?, radio_option);
+        for name in &target.field_order {
+            if !matches!(target.get_type(name)?, FieldType::Button) {
+                assert_eq!(target.get_value_string(name)?, source.get_value_string(name)?, "{}", name);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_acroform() -> Result<(), LoadError> {
         let form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+        assert!(form.acroform().has(b"Fields"));
 
-        let names = form.get_field_names();
+        // An AcroForm stored inline in the catalog is moved into an object of its own
+        let mut doc = build_doc(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+        let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        let acroform_id = doc.objects.get(&catalog_id).unwrap().as_dict().unwrap().get(b"AcroForm").unwrap().as_reference().unwrap();
+        let acroform = doc.objects.remove(&acroform_id).unwrap();
+        doc.objects.get_mut(&catalog_id).unwrap().as_dict_mut().unwrap().set("AcroForm", acroform);
+        let form = Form::load_doc(doc)?;
+        assert_eq!(form.acroform().get(b"Fields")?.as_array()?.len(), 1);
+        let catalog = form.doc.objects.get(&catalog_id).unwrap().as_dict().unwrap();
+        assert!(catalog.get(b"AcroForm")?.as_reference().is_ok());
 
-        assert!(names.len() > 0);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_appearance_mode_regenerate() {
+        let mut form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Name", 0),
+            choice_field(doc, page_id, "Size", ChoiceFlags::COBMO.bits(), &["S", "M"]),
+        ]);
+
+        form.set_text("Name", "Erika").unwrap();
+        assert!(!form.get_field_by_name("Name".to_owned()).has(b"AP"));
+
+        form.set_appearance_mode(AppearanceMode::Regenerate);
+        form.set_text("Name", "Max").unwrap();
+        let content = form.get_appearance_stream("Name", None).unwrap().unwrap();
+        assert!(String::from_utf8(content).unwrap().contains("<4D6178> Tj"));
+        form.set_choice("Size", vec!["M".to_owned()]).unwrap();
+        assert!(form.get_appearance_stream("Size", None).unwrap().is_some());
+        assert!(!form.acroform().has(b"NeedAppearances"));
+    }
+
+    #[test]
+    pub fn test_reset_appearances() {
+        let mut form = build_form(|doc, page_id| {
+            let name_id = text_field(doc, page_id, "Name", 0);
+            let widget_id = doc.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Widget",
+                "P" => page_id,
+                "Rect" => vec![0.into(), 0.into(), 100.into(), 20.into()],
+                "AP" => dictionary! { "N" => Object::Null },
+            });
+            let city_id = doc.add_object(dictionary! {
+                "FT" => "Tx",
+                "T" => text_string("City"),
+                "DV" => Object::string_literal("Berlin"),
+                "Kids" => vec![widget_id.into()],
+            });
+            doc.objects.get_mut(&widget_id).unwrap().as_dict_mut().unwrap().set("Parent", city_id);
+            let name = doc.objects.get_mut(&name_id).unwrap().as_dict_mut().unwrap();
+            name.set("DV", Object::string_literal("Erika"));
+            name.set("AP", dictionary! { "N" => Object::Null });
+            vec![name_id, city_id]
+        });
+
+        // Appearances the viewer relies on are dropped from every widget
+        form.reset("Name").unwrap();
+        form.reset("City").unwrap();
+        assert!(!form.get_field_by_name("Name".to_owned()).has(b"AP"));
+        assert!(form.get_appearance_stream("City", None).unwrap().is_none());
+
+        form.set_appearance_mode(AppearanceMode::Regenerate);
+        form.set_text("Name", "Max").unwrap();
+        form.reset("Name").unwrap();
+        let content = form.get_appearance_stream("Name", None).unwrap().unwrap();
+        assert!(String::from_utf8(content).unwrap().contains("<4572696B61> Tj"));
+        form.reset("City").unwrap();
+        assert!(form.get_appearance_stream("City", None).unwrap().is_some());
+    }
+
+    #[test]
+    pub fn test_check_schema() {
+        let form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Name", 0),
+            check_box(doc, page_id, "Agree", "Yes"),
+            text_field(doc, page_id, "Notes", 0),
+        ]);
+
+        let mut expected = vec![
+            ("Name".to_owned(), FieldType::Text),
+            ("Agree".to_owned(), FieldType::CheckBox),
+            ("Notes".to_owned(), FieldType::Text),
+        ];
+        assert!(form.check_schema(&expected).is_ok());
+
+        expected[1].1 = FieldType::Radio;
+        expected.push(("Email".to_owned(), FieldType::Text));
+        expected.remove(2);
+        assert_eq!(form.check_schema(&expected).unwrap_err(), vec![
+            "field Agree is CheckBox, expected Radio",
+            "missing field Email",
+            "unexpected field Notes",
+        ]);
+    }
+
+    #[test]
+    pub fn test_get_field_page_rotation() {
+        let doc = build_doc(|doc, page_id| {
+            let rotated_page_id = add_page(doc, page_id);
+            doc.objects.get_mut(&rotated_page_id).unwrap().as_dict_mut().unwrap().set("Rotate", -90);
+            let pages_id = doc.objects.get(&page_id).unwrap().as_dict().unwrap().get(b"Parent").unwrap().as_reference().unwrap();
+            doc.objects.get_mut(&pages_id).unwrap().as_dict_mut().unwrap().set("Rotate", 180);
+            vec![
+                text_field(doc, page_id, "Inherited", 0),
+                text_field(doc, rotated_page_id, "Rotated", 0),
+            ]
+        });
+        let form = Form::load_doc(doc).unwrap();
+
+        assert_eq!(form.get_field_page_rotation("Inherited").unwrap(), 180);
+        assert_eq!(form.get_field_page_rotation("Rotated").unwrap(), 270);
+        assert!(form.get_field_page_rotation("Missing").is_err());
+    }
+
+    #[test]
+    pub fn test_rename_fields() {
+        let mut form = build_form(|doc, page_id| vec![
+            text_field(doc, page_id, "Vorname", 0),
+            text_field(doc, page_id, "Nachname", 0),
+            text_field(doc, page_id, "Ort", 0),
+        ]);
+        form.set_text("Vorname", "Erika".to_owned()).unwrap();
+
+        let mut mapping = HashMap::new();
+        mapping.insert("Vorname".to_owned(), "first_name".to_owned());
+        mapping.insert("Nachname".to_owned(), "Ort".to_owned());
+        assert!(matches!(form.rename_fields(&mapping), Err(LoadError::DuplicateName(ref name)) if name == "Ort"));
+        assert_eq!(form.into_iter().collect::<Vec<_>>(), vec!["Vorname", "Nachname", "Ort"]);
+
+        mapping.insert("Nachname".to_owned(), "last_name".to_owned());
+        form.rename_fields(&mapping).unwrap();
+        assert_eq!(form.into_iter().collect::<Vec<_>>(), vec!["first_name", "last_name", "Ort"]);
+        assert!(form.get_type("Vorname").is_err());
+        assert!(form.get_type("Nachname").is_err());
+        assert_eq!(form.get_text("first_name").unwrap(), "Erika");
+
+        let form = Form::load_doc(form.doc).unwrap();
+        assert_eq!(form.into_iter().collect::<Vec<_>>(), vec!["first_name", "last_name", "Ort"]);
+    }
+
+    #[test]
+    pub fn test_set_choice_fuzzy() {
+        let mut form = build_form(|doc, page_id| vec![
+            choice_field(doc, page_id, "Farbe", 0, &["Red", "Blue", "Grün"]),
+            text_field(doc, page_id, "Name", 0),
+        ]);
+
+        assert!(matches!(form.set_choice("Farbe", vec!["blue".to_owned()]), Err(ValueError::InvalidSelection)));
+        form.set_choice_fuzzy("Farbe", vec!["blue".to_owned()]).unwrap();
+        assert_eq!(form.get_value_string("Farbe").unwrap(), "Blue");
+        form.set_choice_fuzzy("Farbe", vec!["GRÜN".to_owned()]).unwrap();
+        assert_eq!(form.get_value_string("Farbe").unwrap(), "Grün");
+
+        assert!(matches!(form.set_choice_fuzzy("Farbe", vec!["Gelb".to_owned()]), Err(ValueError::InvalidSelection)));
+        assert!(matches!(form.set_choice_fuzzy("Name", vec!["blue".to_owned()]), Err(ValueError::TypeMismatch)));
+    }
+
+    #[test]
+    pub fn test_object_count() -> Result<(), LoadError> {
+        let form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+
+        assert_eq!(form.field_count(), form.len());
+        // Every field is at least one object, plus the pages, fonts and appearance streams
+        assert!(form.object_count() > form.field_count());
+        assert!(form.object_count() < 10_000);
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_clear_open_action() -> Result<(), LoadError> {
+        let mut form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+        assert!(!form.has_open_action());
+        let catalog_id = form.doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        form.doc.objects.get_mut(&catalog_id).unwrap().as_dict_mut().unwrap().set("OpenAction", dictionary! {
+            "S" => "JavaScript",
+            "JS" => Object::string_literal("app.alert(\"Hallo\");"),
+        });
+        assert!(form.has_open_action());
+
+        form.clear_open_action();
+
+        assert!(!form.has_open_action());
+        let mut saved = Vec::new();
+        form.save_to(&mut saved).unwrap();
+        assert!(!Form::load_from(&saved[..])?.has_open_action());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_get_state_resolves_names() -> Result<(), LoadError> {
+        let mut form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+        form.set_text("Datum_Eingabe[0]", "01.02.2020".to_owned()).unwrap();
+
+        for name in &["Datum_Eingabe[0]", "Datum_Eingabe", "Formular1[0].Formblatt1_Seite2[0].Datum_Eingabe[0]", "Formular1[0].Formblatt1_Seite2[0].Datum_Eingabe"] {
+            match form.get_state(name) {
+                FieldState::Text { text } => assert_eq!(text, "01.02.2020"),
+                state => panic!("unexpected state {:?} for {}", state, name),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_field_map_json() -> Result<(), LoadError> {
+        let form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+        let (number, generation) = form.get_field_id("Datum_Eingabe[0]").unwrap();
+
+        let map: serde_json::Value = serde_json::from_str(&form.field_map_json()).unwrap();
+
+        assert_eq!(map.as_object().unwrap().len(), form.len());
+        assert_eq!(map["Datum_Eingabe[0]"], serde_json::json!([number, generation]));
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_normalize() -> Result<(), LoadError> {
+        let build = || build_form(|doc, page_id| {
+            let check_box_id = check_box(doc, page_id, "Agree", "Yes");
+            let check_box = doc.objects.get_mut(&check_box_id).unwrap().as_dict_mut().unwrap();
+            check_box.set("V", "Yes");
+            check_box.remove(b"AS");
+            check_box.set("F", 2);
+            vec![check_box_id, text_field(doc, page_id, "Name", 0)]
+        });
+        let with_xfa = |mut form: Form| -> Result<Form, LoadError> {
+            let xfa_id = form.doc.add_object(Stream::new(Dictionary::new(), b"<xdp:xdp/>".to_vec()));
+            form.get_acroform_mut()?.set("XFA", xfa_id);
+            Ok(form)
+        };
+        let mut form = with_xfa(build())?;
+
+        form.normalize(NormalizeOptions::default());
+
+        let check_box = form.get_field_by_name("Agree".to_owned());
+        assert_eq!(check_box.get(b"AS")?.as_name_str()?, "Yes");
+        assert_eq!(check_box.get(b"F")?.as_i64()?, 6);
+        assert_eq!(form.get_field_by_name("Name".to_owned()).get(b"F")?.as_i64()?, 4);
+        assert!(matches!(form.acroform().get(b"NeedAppearances"), Ok(&Object::Boolean(true))));
+        assert!(!form.has_xfa());
+
+        let mut form = with_xfa(build())?;
+        form.normalize(NormalizeOptions { drop_xfa: false, sync_appearance_states: false, ..NormalizeOptions::default() });
+        assert!(form.has_xfa());
+        assert!(!form.get_field_by_name("Agree".to_owned()).has(b"AS"));
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_check_box_with_unusual_off_state() {
+        let mut form = build_form(|doc, page_id| {
+            let on_id = doc.add_object(Stream::new(Dictionary::new(), b"0 g 1 1 8 8 re f".to_vec()));
+            let off_id = doc.add_object(Stream::new(Dictionary::new(), b" \n".to_vec()));
+            vec![doc.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Widget",
+                "P" => page_id,
+                "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+                "FT" => "Btn",
+                "T" => text_string("Agree"),
+                "AP" => dictionary! { "N" => dictionary! { "Nein" => off_id, "Ja" => on_id } },
+                "AS" => "Nein",
+                "V" => "Nein",
+            })]
+        });
+        assert!(matches!(form.get_state("Agree"), FieldState::CheckBox { is_checked: false }));
+
+        assert!(form.toggle_check_box("Agree").unwrap());
+        assert!(matches!(form.get_state("Agree"), FieldState::CheckBox { is_checked: true }));
+        assert_eq!(form.get_field_by_name("Agree".to_owned()).get(b"AS").unwrap().as_name_str().unwrap(), "Ja");
+
+        form.set_check_box("Agree", false).unwrap();
+        assert!(matches!(form.get_state("Agree"), FieldState::CheckBox { is_checked: false }));
+        assert_eq!(form.get_field_by_name("Agree".to_owned()).get(b"V").unwrap().as_name_str().unwrap(), "Nein");
+
+        let state = |form: &Form| form.get_field_by_name("Agree".to_owned()).get(b"AS").unwrap().as_name_str().unwrap().to_owned();
+        let field_id = form.get_field_id("Agree").unwrap();
+        form.doc.objects.get_mut(&field_id).unwrap().as_dict_mut().unwrap().set("V", "Off");
+        form.normalize(NormalizeOptions::default());
+        assert_eq!(state(&form), "Nein");
+
+        form.set_check_box("Agree", true).unwrap();
+        form.reset("Agree").unwrap();
+        assert_eq!(state(&form), "Nein");
+        assert!(matches!(form.get_state("Agree"), FieldState::CheckBox { is_checked: false }));
+    }
+
+    #[test]
+    pub fn test_set_text_truncating() {
+        let mut form = build_form(|doc, page_id| {
+            let zip_id = text_field(doc, page_id, "PLZ", TextFlags::COMB.bits());
+            doc.objects.get_mut(&zip_id).unwrap().as_dict_mut().unwrap().set("MaxLen", 5);
+            vec![zip_id, text_field(doc, page_id, "Ort", 0)]
+        });
+
+        form.set_text_truncating("PLZ", "391040").unwrap();
+        assert_eq!(form.get_text("PLZ").unwrap(), "39104");
+        form.set_text_truncating("PLZ", "Ä1234€").unwrap();
+        assert_eq!(form.get_text("PLZ").unwrap(), "Ä1234");
+        form.set_text_truncating("PLZ", "391").unwrap();
+        assert_eq!(form.get_text("PLZ").unwrap(), "391");
+        form.set_text_truncating("Ort", "Magdeburg").unwrap();
+        assert_eq!(form.get_text("Ort").unwrap(), "Magdeburg");
+    }
+
+    #[test]
+    pub fn test_is_tagged() -> Result<(), LoadError> {
+        let mut form = Form::load("./tests/assets/Formblatt_1.pdf")?;
+        assert!(form.is_tagged());
+
+        let catalog_id = form.doc.trailer.get(b"Root")?.as_reference()?;
+        form.doc.objects.get_mut(&catalog_id).unwrap().as_dict_mut()?.set("MarkInfo", dictionary! { "Marked" => false });
+        assert!(!form.is_tagged());
+
+        let form = build_form(|doc, page_id| vec![text_field(doc, page_id, "Name", 0)]);
+        assert!(!form.is_tagged());
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_set_choice_by_index() {
+        let mut form = build_form(|doc, page_id| vec![
+            choice_field(doc, page_id, "Sprachen", ChoiceFlags::MULTISELECT.bits(), &["Deutsch", "Englisch", "Französisch"]),
+            choice_field(doc, page_id, "Anrede", 0, &["", "Frau", "Herr"]),
+        ]);
+
+        form.set_choice_by_index("Sprachen", vec![0, 2]).unwrap();
+        match form.get_state("Sprachen") {
+            FieldState::ListBox { selected, .. } => assert_eq!(selected, vec!["Deutsch", "Französisch"]),
+            state => panic!("unexpected state {:?}", state),
+        }
+        form.set_choice_by_index("Anrede", vec![2]).unwrap();
+        match form.get_state("Anrede") {
+            FieldState::ListBox { selected, .. } => assert_eq!(selected, vec!["Herr"]),
+            state => panic!("unexpected state {:?}", state),
+        }
+
+        assert!(matches!(form.set_choice_by_index("Sprachen", vec![3]), Err(ValueError::InvalidSelection)));
+        assert!(matches!(form.set_choice_by_index("Anrede", vec![1, 2]), Err(ValueError::TooManySelected)));
+    }
+
+    #[test]
+    pub fn test_parse_selected_indices_with_empty_option() {
+        let form = build_form(|doc, page_id| {
+            let list_id = choice_field(doc, page_id, "List", 0, &["", "A", "B"]);
+            doc.objects.get_mut(&list_id).unwrap().as_dict_mut().unwrap().set("I", vec![1.into()]);
+            vec![list_id]
+        });
+
+        match form.get_state("List") {
+            FieldState::ListBox { selected, options, .. } => {
+                assert_eq!(selected, vec!["A"]);
+                assert_eq!(options, vec!["A", "B"]);
+            }
+            state => panic!("unexpected state {:?}", state),
+        }
+    }
+
+    #[test]
+    pub fn test_set_choice_indices_with_empty_option() {
+        let mut form = build_form(|doc, page_id| vec![
+            choice_field(doc, page_id, "List", 0, &["", "A", "B"]),
+        ]);
+
+        form.set_choice("List", vec!["B".to_owned()]).unwrap();
+
+        let indices = form.get_field_by_name("List".to_owned()).get(b"I").unwrap().as_array().unwrap().clone();
+        assert!(matches!(indices[..], [Object::Integer(2)]));
+    }
+}
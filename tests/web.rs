@@ -5,12 +5,25 @@ extern crate wasm_bindgen_test;
 use wasm_bindgen_test::*;
 
 use pdfformfill;
-use wasm_bindgen::prelude::*;
-use std::io::Read;
+use std::collections::HashMap;
 
 wasm_bindgen_test_configure!(run_in_browser);
 
 #[wasm_bindgen_test]
 fn pass() {
     assert_eq!(1 + 1, 2);
-}
\ No newline at end of file
+}
+
+#[wasm_bindgen_test]
+fn fill_rejects_with_field_error() {
+    let mut form = pdfformfill::load_form(include_bytes!("assets/Formblatt_1.pdf"));
+
+    let mut fields = HashMap::new();
+    fields.insert("Einnahmen_Auswahl", "not an option");
+    let error = form.fill(serde_wasm_bindgen::to_value(&fields).unwrap()).unwrap_err();
+
+    let error: HashMap<String, String> = serde_wasm_bindgen::from_value(error).unwrap();
+    assert_eq!(error["field"], "Einnahmen_Auswahl[0]");
+    assert_eq!(error["value"], "not an option");
+    assert_eq!(error["error"], "One or more selected values are not valid choices");
+}